@@ -0,0 +1,1188 @@
+use crate::{CloneFn, LookaheadError, SliceStorage, Storage};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Bound, RangeBounds};
+use core::slice::GetDisjointMutError;
+use fallible_iterator::FallibleIterator;
+
+/// A lookahead-buffer implementation for [fallible_iterator].
+/// Allows peeking into a [FallibleIterator].
+///
+/// Consumes the iterator lazily, only if the queue is empty and items are needed for peeking.
+///
+/// The queue is a [VecDeque] by default, but can be any [Storage].
+pub struct LookaheadBuffer<I: FallibleIterator, S = VecDeque<<I as FallibleIterator>::Item>> {
+    iter: I,
+    queue: S,
+
+    /// Changes made while at least one checkpoint is live, oldest first.
+    journal: Vec<Edit<I::Item>>,
+
+    /// Live checkpoints as `(id, journal.len() at creation)`, oldest first.
+    checkpoints: Vec<(usize, usize)>,
+    next_checkpoint_id: usize,
+
+    /// Clones consumed items into `journal` and `history`. Set by [LookaheadBuffer::checkpoint]
+    /// and [LookaheadBuffer::with_history].
+    clone_item: Option<CloneFn<I::Item>>,
+
+    /// The error the iterator failed with, located right after the last queued item.
+    /// Only stored if sticky errors are enabled.
+    error: Option<I::Error>,
+
+    /// Set by [LookaheadBuffer::with_sticky_errors].
+    clone_error: Option<CloneFn<I::Error>>,
+
+    /// Whether the iterator has returned `Ok(None)`.
+    exhausted: bool,
+
+    /// The position of the next item.
+    position: Position,
+
+    /// Set by [LookaheadBuffer::with_width]. Every item is one unit wide if absent.
+    width: Option<fn(&I::Item) -> usize>,
+
+    /// The most recently consumed items, oldest first.
+    history: VecDeque<I::Item>,
+
+    /// The maximum length of `history`. Set by [LookaheadBuffer::with_history].
+    history_limit: usize,
+
+    /// The maximum number of items to look ahead, with the function creating the error
+    /// if exceeded. Set by [LookaheadBuffer::set_limit].
+    limit: Option<(usize, LimitFn<I::Error>)>,
+}
+
+/// Creates the error for `requested` items exceeding `limit`.
+type LimitFn<E> = fn(usize, usize) -> E;
+
+/// The position of an item in the stream of a [LookaheadBuffer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Position {
+    /// The number of items consumed before this item.
+    pub index: usize,
+
+    /// The sum of the widths of all items consumed before this item. Equal to
+    /// [Position::index] unless a width function was set with [LookaheadBuffer::with_width].
+    pub offset: usize,
+}
+
+impl Position {
+    #[inline]
+    const fn advance(&mut self, width: usize) {
+        self.index += 1;
+        self.offset += width;
+    }
+
+    #[inline]
+    const fn retreat(&mut self, width: usize) {
+        self.index = self.index.saturating_sub(1);
+        self.offset = self.offset.saturating_sub(width);
+    }
+}
+
+/// A change to a [LookaheadBuffer] that [LookaheadBuffer::rewind] has to undo.
+#[derive(Clone)]
+enum Edit<T> {
+    /// The item was consumed, evicting the second item from the history.
+    Consumed(T, Option<T>),
+
+    /// An item was pushed to the front with [LookaheadBuffer::push_front], which was at
+    /// the contained position before.
+    Pushed(Position),
+
+    /// The item was removed from the queue at the contained index without being consumed.
+    Removed(usize, T),
+
+    /// An item was inserted into the queue at the contained index without changing the position.
+    Inserted(usize),
+}
+
+/// An opaque marker into a [LookaheadBuffer], created by [LookaheadBuffer::checkpoint].
+///
+/// Pass it to [LookaheadBuffer::rewind] to go back to the position it was created at or
+/// to [LookaheadBuffer::commit] to release it.
+#[derive(Debug, PartialEq, Eq)]
+#[must_use = "a live checkpoint keeps all consumed items in memory"]
+pub struct Checkpoint {
+    id: usize,
+}
+
+/// The error returned when rewinding to or committing a [Checkpoint] that was already released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidCheckpoint;
+
+impl fmt::Display for InvalidCheckpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("checkpoint was already released")
+    }
+}
+
+impl core::error::Error for InvalidCheckpoint {}
+
+/// An iterator over the lookahead window of a [LookaheadBuffer], created by
+/// [LookaheadBuffer::peek_iter].
+///
+/// This does not implement [FallibleIterator], because pulling a new item may move the items
+/// already returned. Instead, each item borrows the [PeekIter] until the next call to
+/// [PeekIter::next].
+pub struct PeekIter<'a, I: FallibleIterator, S> {
+    buffer: &'a mut LookaheadBuffer<I, S>,
+    index: usize,
+}
+
+impl<I: FallibleIterator, S: Storage<I::Item>> PeekIter<'_, I, S> {
+    /// Returns the next item ahead, pulling it from the iterator if necessary.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<&I::Item>, I::Error> {
+        let token = self.buffer.peek_n(self.index)?;
+
+        if token.is_some() {
+            self.index += 1;
+        }
+
+        Ok(token)
+    }
+
+    /// Returns the lookahead index of the item the next call to [PeekIter::next] returns.
+    #[inline]
+    #[must_use]
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// A predicate in a sequence passed to [LookaheadBuffer::starts_with_by] and
+/// [LookaheadBuffer::eat_sequence_by].
+pub type Predicate<'a, T> = &'a dyn Fn(&T) -> bool;
+
+/// The result of matching a sequence with [LookaheadBuffer::starts_with] and related methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceMatch {
+    /// The whole sequence matched.
+    Full,
+
+    /// Only the contained number of leading items matched.
+    Partial(usize),
+}
+
+impl SequenceMatch {
+    /// Returns `true` if the whole sequence matched.
+    #[inline]
+    #[must_use]
+    pub const fn is_full(self) -> bool {
+        matches!(self, Self::Full)
+    }
+}
+
+/// The error returned by [LookaheadBuffer::expect].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectError<E, X> {
+    /// The iterator failed.
+    Source(E),
+
+    /// The next item did not match. Contains the user-supplied error.
+    Unexpected(X),
+}
+
+impl<E: fmt::Display, X: fmt::Display> fmt::Display for ExpectError<E, X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Source(error) => error.fmt(f),
+            Self::Unexpected(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: core::error::Error, X: core::error::Error> core::error::Error for ExpectError<E, X> {}
+
+/// The error returned by [LookaheadBuffer::peek_many_mut].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeekManyError<E> {
+    /// The iterator failed.
+    Source(E),
+
+    /// The iterator ended before an index was reached.
+    IndexOutOfBounds,
+
+    /// An index was given more than once.
+    OverlappingIndices,
+}
+
+impl<E: fmt::Display> fmt::Display for PeekManyError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Source(error) => error.fmt(f),
+            Self::IndexOutOfBounds => f.write_str("index is past the end of the stream"),
+            Self::OverlappingIndices => f.write_str("an index was given more than once"),
+        }
+    }
+}
+
+impl<E: core::error::Error> core::error::Error for PeekManyError<E> {}
+
+impl<I: FallibleIterator> LookaheadBuffer<I> {
+    /// Create a new, empty [LookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub const fn new(iter: I) -> Self {
+        Self::from_parts(iter, VecDeque::new())
+    }
+}
+
+impl<I: FallibleIterator, S: Storage<I::Item>> LookaheadBuffer<I, S> {
+    /// Create a new [LookaheadBuffer] backed by `queue`. Items already in `queue` are
+    /// yielded before any item of `iter`. This is the inverse of [LookaheadBuffer::destructure].
+    #[inline]
+    #[must_use]
+    pub const fn from_parts(iter: I, queue: S) -> Self {
+        Self {
+            iter,
+            queue,
+            journal: Vec::new(),
+            checkpoints: Vec::new(),
+            next_checkpoint_id: 0,
+            clone_item: None,
+            error: None,
+            clone_error: None,
+            exhausted: false,
+            position: Position {
+                index: 0,
+                offset: 0,
+            },
+            width: None,
+            history: VecDeque::new(),
+            history_limit: 0,
+            limit: None,
+        }
+    }
+
+    /// Sets a function that returns the width of an item, for example its length in bytes.
+    /// [Position::offset] is then the sum of the widths of all consumed items.
+    ///
+    /// This should be set before any item is consumed.
+    ///
+    /// `width` is a function pointer, not a closure, so that the buffer stays [Clone] and
+    /// [Send] without boxing. Closures that capture state, like a source map, are rejected;
+    /// compute such widths up front and store them in the items instead, for example as spans.
+    #[inline]
+    #[must_use]
+    pub fn with_width(mut self, width: fn(&I::Item) -> usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Returns the maximum number of items that can be looked ahead, if set with
+    /// [LookaheadBuffer::set_limit].
+    #[inline]
+    #[must_use]
+    pub fn limit(&self) -> Option<usize> {
+        self.limit.map(|(limit, _)| limit)
+    }
+
+    /// Returns the position of the next item.
+    #[inline]
+    #[must_use]
+    pub const fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Enables sticky errors.
+    ///
+    /// Once the iterator fails, the error is stored at its position in the stream and the
+    /// iterator is never polled again. Items before the error can still be peeked and
+    /// consumed; every peek or consumption that reaches the error returns a clone of it.
+    #[inline]
+    #[must_use]
+    pub fn with_sticky_errors(mut self) -> Self
+    where
+        I::Error: Clone,
+    {
+        self.clone_error = Some(I::Error::clone);
+        self
+    }
+
+    /// Returns `true` if the iterator has signaled its end by returning `Ok(None)`.
+    /// Queued items may still be left.
+    ///
+    /// Once exhausted, the iterator is not polled again until [LookaheadBuffer::reset_exhaustion]
+    /// is called.
+    #[inline]
+    #[must_use]
+    pub const fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Allows the iterator to be polled again after it was exhausted. This is useful
+    /// for resumable sources, like a file that is still being written to.
+    #[inline]
+    pub const fn reset_exhaustion(&mut self) {
+        self.exhausted = false;
+    }
+
+    /// Returns the stored error, if sticky errors are enabled and the iterator has failed.
+    #[inline]
+    #[must_use]
+    pub const fn error(&self) -> Option<&I::Error> {
+        self.error.as_ref()
+    }
+
+    /// Destructure `self` into the [FallibleIterator] and [Storage].
+    #[inline]
+    #[must_use]
+    pub fn destructure(self) -> (I, S) {
+        let Self { queue, iter, .. } = self;
+        (iter, queue)
+    }
+
+    /// Returns a reference to the queue.
+    #[inline]
+    #[must_use]
+    pub const fn queue(&self) -> &S {
+        &self.queue
+    }
+
+    /// Returns a mutable reference to the queue.
+    #[inline]
+    #[must_use]
+    pub const fn queue_mut(&mut self) -> &mut S {
+        &mut self.queue
+    }
+
+    /// Returns a reference to the underlying iterator.
+    #[inline]
+    #[must_use]
+    pub const fn iter(&self) -> &I {
+        &self.iter
+    }
+
+    /// Returns a mutable reference to the underlying iterator.
+    #[inline]
+    #[must_use]
+    pub const fn iter_mut(&mut self) -> &mut I {
+        &mut self.iter
+    }
+
+    /// Tries to ensure that `n` items are in the queue. If, after a call to this function,
+    /// this is not the case, then this function could not pull any more items from the iterator.
+    /// Fails if `n` exceeds the limit and items would have to be pulled.
+    ///
+    /// Never pulls more items than the storage can hold. If a limit is set, exceeding
+    /// [Storage::max_len] fails like exceeding the limit.
+    #[inline]
+    fn try_ensure(&mut self, n: usize) -> Result<(), I::Error> {
+        let max_len = self.queue.max_len();
+
+        if n > self.queue.len()
+            && let Some((limit, limit_exceeded)) = self.limit
+        {
+            let limit = max_len.map_or(limit, |max_len| limit.min(max_len));
+
+            if n > limit {
+                return Err(limit_exceeded(n, limit));
+            }
+        }
+
+        let n = max_len.map_or(n, |max_len| n.min(max_len));
+
+        for _ in 0..n.saturating_sub(self.queue.len()) {
+            if let Some(token) = self.pull()? {
+                self.queue.push_back(token);
+            } else {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pulls the next item from the iterator, storing the error if sticky errors are enabled.
+    #[inline]
+    fn pull(&mut self) -> Result<Option<I::Item>, I::Error> {
+        if let (Some(error), Some(clone_error)) = (&self.error, self.clone_error) {
+            return Err(clone_error(error));
+        }
+
+        if self.exhausted {
+            return Ok(None);
+        }
+
+        match (self.iter.next(), self.clone_error) {
+            (Ok(None), _) => {
+                self.exhausted = true;
+                Ok(None)
+            }
+            (Err(error), Some(clone_error)) => Err(clone_error(self.error.insert(error))),
+            (result, _) => result,
+        }
+    }
+
+    /// Returns `true` if bulk operations can be forwarded to the underlying iterator.
+    #[inline]
+    fn can_delegate(&self) -> bool {
+        self.checkpoints.is_empty()
+            && self.clone_error.is_none()
+            && self.width.is_none()
+            && self.history_limit == 0
+            && !self.exhausted
+    }
+
+    /// Peeks into the next `N` items. If less than `N` items will be yielded by the iterator
+    /// (or are already partially yielded into the queue), then the remaining slots in the
+    /// array will be [None].
+    pub fn peek_multiple<const N: usize>(&mut self) -> Result<[Option<&I::Item>; N], I::Error> {
+        self.try_ensure(N)?;
+        let mut pack = [None; N];
+
+        for (i, slot) in pack.iter_mut().enumerate() {
+            *slot = self.queue.get(i);
+        }
+
+        Ok(pack)
+    }
+
+    /// Peeks into the next `N` items, only if all of them exist. Returns [None] if the iterator
+    /// yields less than `N` items.
+    pub fn peek_exact<const N: usize>(&mut self) -> Result<Option<[&I::Item; N]>, I::Error> {
+        self.try_ensure(N)?;
+
+        if self.queue.len() < N {
+            return Ok(None);
+        }
+
+        Ok(Some(core::array::from_fn(|i| {
+            self.queue.get(i).expect("the queue has at least N items")
+        })))
+    }
+
+    /// Consumes the next `N` items, only if all of them exist. Returns [None] and consumes
+    /// nothing if the iterator yields less than `N` items.
+    pub fn next_exact<const N: usize>(&mut self) -> Result<Option<[I::Item; N]>, I::Error> {
+        self.try_ensure(N)?;
+
+        if self.queue.len() < N {
+            return Ok(None);
+        }
+
+        Ok(Some(core::array::from_fn(|_| {
+            let token = self
+                .queue
+                .pop_front()
+                .expect("the queue has at least N items");
+
+            self.consume(token)
+        })))
+    }
+
+    /// Peeks into the next item. Does not advance. Equivalent to `self.peek_n(0)`.
+    #[inline]
+    pub fn peek(&mut self) -> Result<Option<&I::Item>, I::Error> {
+        self.peek_n(0)
+    }
+
+    /// Peeks into the next item, mutably. Does not advance. Equivalent to `self.peek_n_mut(0)`.
+    #[inline]
+    pub fn peek_mut(&mut self) -> Result<Option<&mut I::Item>, I::Error> {
+        self.peek_n_mut(0)
+    }
+
+    /// Peeks into the nth item, with n=0 being the next item.
+    #[inline]
+    pub fn peek_n(&mut self, n: usize) -> Result<Option<&I::Item>, I::Error> {
+        self.try_ensure(n.saturating_add(1))?;
+        Ok(self.queue.get(n))
+    }
+
+    /// Peeks into the nth item, mutably, with n=0 being the next item.
+    #[inline]
+    pub fn peek_n_mut(&mut self, n: usize) -> Result<Option<&mut I::Item>, I::Error> {
+        self.try_ensure(n.saturating_add(1))?;
+        Ok(self.queue.get_mut(n))
+    }
+
+    /// Returns a [PeekIter] over the lookahead window, starting at the next item.
+    /// Items are only pulled from the iterator as the [PeekIter] advances.
+    #[inline]
+    pub const fn peek_iter(&mut self) -> PeekIter<'_, I, S> {
+        PeekIter {
+            buffer: self,
+            index: 0,
+        }
+    }
+
+    /// Returns the lookahead index of the first item that `predicate` returns `true` for,
+    /// with 0 being the next item. Nothing is consumed.
+    pub fn position_ahead(
+        &mut self,
+        mut predicate: impl FnMut(&I::Item) -> bool,
+    ) -> Result<Option<usize>, I::Error> {
+        let mut iter = self.peek_iter();
+
+        while let Some(token) = iter.next()? {
+            if predicate(token) {
+                return Ok(Some(iter.index - 1));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the first item ahead that `predicate` returns `true` for. Nothing is consumed.
+    pub fn find_ahead(
+        &mut self,
+        predicate: impl FnMut(&I::Item) -> bool,
+    ) -> Result<Option<&I::Item>, I::Error> {
+        Ok(self
+            .position_ahead(predicate)?
+            .and_then(|index| self.queue.get(index)))
+    }
+
+    /// Consumes the next item, returning it.
+    ///
+    /// This is the same as [FallibleIterator::next], but does not require the trait to be in scope.
+    #[inline]
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<I::Item>, I::Error> {
+        let token = match self.queue.pop_front() {
+            Some(token) => token,
+            None => match self.pull()? {
+                Some(token) => token,
+                None => return Ok(None),
+            },
+        };
+
+        Ok(Some(self.consume(token)))
+    }
+
+    /// Consumes the next item like [LookaheadBuffer::next], also returning its position.
+    #[inline]
+    pub fn next_with_pos(&mut self) -> Result<Option<(Position, I::Item)>, I::Error> {
+        let position = self.position;
+        Ok(self.next()?.map(|token| (position, token)))
+    }
+
+    /// Peeks into the nth item like [LookaheadBuffer::peek_n], also returning its position.
+    pub fn peek_n_with_pos(&mut self, n: usize) -> Result<Option<(Position, &I::Item)>, I::Error> {
+        self.try_ensure(n.saturating_add(1))?;
+
+        let Some(token) = self.queue.get(n) else {
+            return Ok(None);
+        };
+
+        let offset = match self.width {
+            Some(width) => (0..n).filter_map(|i| self.queue.get(i)).map(width).sum(),
+            None => n,
+        };
+
+        let position = Position {
+            index: self.position.index + n,
+            offset: self.position.offset + offset,
+        };
+
+        Ok(Some((position, token)))
+    }
+
+    /// Consumes and returns the next item if `predicate` returns `true` for it.
+    #[inline]
+    pub fn next_if(
+        &mut self,
+        predicate: impl FnOnce(&I::Item) -> bool,
+    ) -> Result<Option<I::Item>, I::Error> {
+        match self.peek()? {
+            Some(token) if predicate(token) => self.next(),
+            _ => Ok(None),
+        }
+    }
+
+    /// Consumes and returns the next item if it is equal to `expected`.
+    #[inline]
+    pub fn next_if_eq<T: ?Sized>(&mut self, expected: &T) -> Result<Option<I::Item>, I::Error>
+    where
+        I::Item: PartialEq<T>,
+    {
+        self.next_if(|token| token == expected)
+    }
+
+    /// Consumes the next item if `f` returns [Some] for it, returning the mapped value.
+    #[inline]
+    pub fn next_if_map<R>(
+        &mut self,
+        f: impl FnOnce(&I::Item) -> Option<R>,
+    ) -> Result<Option<R>, I::Error> {
+        let Some(mapped) = self.peek()?.and_then(f) else {
+            return Ok(None);
+        };
+
+        self.next()?;
+        Ok(Some(mapped))
+    }
+
+    /// Consumes and returns the next item if `predicate` returns `true` for it. Otherwise,
+    /// `make_error` is called with the next item (or [None] at the end) and its result returned.
+    pub fn expect<X>(
+        &mut self,
+        predicate: impl FnOnce(&I::Item) -> bool,
+        make_error: impl FnOnce(Option<&I::Item>) -> X,
+    ) -> Result<I::Item, ExpectError<I::Error, X>> {
+        match self.peek().map_err(ExpectError::Source)? {
+            Some(token) if predicate(token) => {}
+            token => return Err(ExpectError::Unexpected(make_error(token))),
+        }
+
+        match self.next() {
+            Ok(Some(token)) => Ok(token),
+            Ok(None) => unreachable!("the next item was peeked"),
+            Err(error) => Err(ExpectError::Source(error)),
+        }
+    }
+
+    /// Checks whether the next items are equal to `sequence`. Nothing is consumed.
+    #[inline]
+    pub fn starts_with<P: PartialEq<I::Item>>(
+        &mut self,
+        sequence: &[P],
+    ) -> Result<SequenceMatch, I::Error> {
+        self.match_sequence(sequence.len(), |i, token| sequence[i] == *token)
+    }
+
+    /// Checks whether the next items each satisfy the respective predicate in `sequence`.
+    /// Nothing is consumed.
+    #[inline]
+    pub fn starts_with_by(
+        &mut self,
+        sequence: &[Predicate<'_, I::Item>],
+    ) -> Result<SequenceMatch, I::Error> {
+        self.match_sequence(sequence.len(), |i, token| sequence[i](token))
+    }
+
+    /// Consumes the next items if all of them are equal to `sequence`, like
+    /// [LookaheadBuffer::starts_with]. On a partial match, nothing is consumed.
+    pub fn eat_sequence<P: PartialEq<I::Item>>(
+        &mut self,
+        sequence: &[P],
+    ) -> Result<SequenceMatch, I::Error> {
+        let result = self.starts_with(sequence)?;
+        self.eat_match(result, sequence.len())
+    }
+
+    /// Consumes the next items if all of them satisfy the respective predicate in `sequence`,
+    /// like [LookaheadBuffer::starts_with_by]. On a partial match, nothing is consumed.
+    pub fn eat_sequence_by(
+        &mut self,
+        sequence: &[Predicate<'_, I::Item>],
+    ) -> Result<SequenceMatch, I::Error> {
+        let result = self.starts_with_by(sequence)?;
+        self.eat_match(result, sequence.len())
+    }
+
+    /// Counts how many of the next `len` items match, pulling only up to the first mismatch.
+    fn match_sequence(
+        &mut self,
+        len: usize,
+        mut matches: impl FnMut(usize, &I::Item) -> bool,
+    ) -> Result<SequenceMatch, I::Error> {
+        for i in 0..len {
+            match self.peek_n(i)? {
+                Some(token) if matches(i, token) => {}
+                _ => return Ok(SequenceMatch::Partial(i)),
+            }
+        }
+
+        Ok(SequenceMatch::Full)
+    }
+
+    /// Consumes `len` items if `result` is a full match.
+    #[inline]
+    fn eat_match(&mut self, result: SequenceMatch, len: usize) -> Result<SequenceMatch, I::Error> {
+        if result.is_full() {
+            self.advance_by(len)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Advances the position, keeps a copy of a consumed item if any checkpoint is live
+    /// and records it in the history.
+    #[inline]
+    fn consume(&mut self, token: I::Item) -> I::Item {
+        self.position
+            .advance(self.width.map_or(1, |width| width(&token)));
+
+        let Some(clone_item) = self.clone_item else {
+            return token;
+        };
+
+        let mut evicted = None;
+
+        if self.history_limit > 0 {
+            if self.history.len() == self.history_limit {
+                evicted = self.history.pop_front();
+            }
+
+            self.history.push_back(clone_item(&token));
+        }
+
+        if !self.checkpoints.is_empty() {
+            self.journal
+                .push(Edit::Consumed(clone_item(&token), evicted));
+        }
+
+        token
+    }
+
+    /// Returns the nth most recently consumed item, with n=0 being the last consumed item.
+    ///
+    /// Only the items kept by [LookaheadBuffer::with_history] are available.
+    #[inline]
+    #[must_use]
+    pub fn look_back(&self, n: usize) -> Option<&I::Item> {
+        self.history
+            .len()
+            .checked_sub(n.saturating_add(1))
+            .and_then(|index| self.history.get(index))
+    }
+
+    /// Returns the most recently consumed items, oldest first.
+    #[inline]
+    #[must_use]
+    pub const fn history(&self) -> &VecDeque<I::Item> {
+        &self.history
+    }
+
+    /// Skips the next `n` items, consuming queued items first. Returns the number of
+    /// items that were actually skipped, which is less than `n` only if the iterator ran out.
+    pub fn advance_by(&mut self, n: usize) -> Result<usize, I::Error> {
+        if !self.can_delegate() {
+            for skipped in 0..n {
+                if self.next()?.is_none() {
+                    return Ok(skipped);
+                }
+            }
+
+            return Ok(n);
+        }
+
+        let queued = n.min(self.queue.len());
+
+        for _ in 0..queued {
+            self.queue.pop_front();
+        }
+
+        for skipped in queued..n {
+            match self.pull() {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    self.skip_positions(skipped);
+                    return Ok(skipped);
+                }
+                Err(error) => {
+                    self.skip_positions(skipped);
+                    return Err(error);
+                }
+            }
+        }
+
+        self.skip_positions(n);
+        Ok(n)
+    }
+
+    /// Puts `token` back in front of the queue, so that it is the next item.
+    ///
+    /// This moves the position back by one item, as if `token` had not been consumed yet.
+    /// A live checkpoint undoes the push when rewound to.
+    pub fn push_front(&mut self, token: I::Item) {
+        self.record(Edit::Pushed(self.position));
+        self.position
+            .retreat(self.width.map_or(1, |width| width(&token)));
+        self.queue.push_front(token);
+    }
+
+    /// Puts `tokens` back in front of the queue, like [LookaheadBuffer::push_front]. Afterward,
+    /// the first item of `tokens` is the next item, followed by the rest in order.
+    pub fn unread_many<T>(&mut self, tokens: T)
+    where
+        T: IntoIterator<Item = I::Item, IntoIter: DoubleEndedIterator>,
+    {
+        for token in tokens.into_iter().rev() {
+            self.push_front(token);
+        }
+    }
+
+    /// Replaces the next `n` items with `tokens`, so that the first item of `tokens` is the
+    /// next item. Unlike [LookaheadBuffer::unread_many], the position does not change.
+    ///
+    /// Returns the number of items removed, which is less than `n` only if the iterator ran out.
+    pub fn replace_next<T>(&mut self, n: usize, tokens: T) -> Result<usize, I::Error>
+    where
+        T: IntoIterator<Item = I::Item>,
+    {
+        self.splice(..n, tokens)
+    }
+
+    /// Replaces the items in `range` of the lookahead window, with 0 being the next item,
+    /// by `tokens`, keeping their order. Only the items up to the end of `range` are pulled
+    /// from the iterator. An open end means the end of the items already buffered, so
+    /// `splice(n.., tokens)` never pulls more than `n` items. The position does not change,
+    /// since no item is consumed.
+    ///
+    /// Returns the number of items removed, which is less than the length of `range` only if
+    /// the iterator ran out. If it ran out before the start of `range`, `tokens` are appended.
+    pub fn splice<T>(
+        &mut self,
+        range: impl RangeBounds<usize>,
+        tokens: T,
+    ) -> Result<usize, I::Error>
+    where
+        T: IntoIterator<Item = I::Item>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.queue.len().max(start),
+        };
+
+        self.try_ensure(end)?;
+        let start = start.min(self.queue.len());
+        let end = end.clamp(start, self.queue.len());
+
+        for _ in start..end {
+            if let Some(token) = self.queue.remove(start) {
+                self.record(Edit::Removed(start, token));
+            }
+        }
+
+        for (index, token) in (start..).zip(tokens) {
+            self.queue.insert(index, token);
+            self.record(Edit::Inserted(index));
+        }
+
+        Ok(end - start)
+    }
+
+    /// Records `edit` in the journal if any checkpoint is live.
+    #[inline]
+    fn record(&mut self, edit: Edit<I::Item>) {
+        if !self.checkpoints.is_empty() {
+            self.journal.push(edit);
+        }
+    }
+
+    /// Goes back to the position `checkpoint` was created at. All items consumed since then
+    /// will be yielded again and the history is restored.
+    ///
+    /// This releases `checkpoint` and all checkpoints created after it. If `checkpoint` was
+    /// already released, [InvalidCheckpoint] is returned and the buffer is left untouched.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) -> Result<(), InvalidCheckpoint> {
+        let mark = self.release(checkpoint)?;
+
+        for edit in self.journal.drain(mark..).rev() {
+            match edit {
+                Edit::Consumed(token, evicted) => {
+                    self.position
+                        .retreat(self.width.map_or(1, |width| width(&token)));
+                    self.queue.push_front(token);
+
+                    if self.history.pop_back().is_some()
+                        && let Some(evicted) = evicted
+                    {
+                        self.history.push_front(evicted);
+                    }
+                }
+                Edit::Pushed(position) => {
+                    self.queue.pop_front();
+                    self.position = position;
+                }
+                Edit::Removed(index, token) => self.queue.insert(index, token),
+                Edit::Inserted(index) => _ = self.queue.remove(index),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Releases `checkpoint` and all checkpoints created after it, keeping the current position.
+    ///
+    /// Once no checkpoint is live, consumed items are no longer retained. If `checkpoint` was
+    /// already released, [InvalidCheckpoint] is returned.
+    pub fn commit(&mut self, checkpoint: Checkpoint) -> Result<(), InvalidCheckpoint> {
+        self.release(checkpoint)?;
+
+        if self.checkpoints.is_empty() {
+            self.journal.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Advances the position by `n` items that were skipped while [LookaheadBuffer::can_delegate].
+    #[inline]
+    const fn skip_positions(&mut self, n: usize) {
+        self.position.index += n;
+        self.position.offset += n;
+    }
+
+    /// Returns the number of live checkpoints.
+    #[inline]
+    #[must_use]
+    pub fn checkpoints(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Removes `checkpoint` and all newer ones from the stack, returning the length
+    /// `journal` had when `checkpoint` was created.
+    fn release(&mut self, checkpoint: Checkpoint) -> Result<usize, InvalidCheckpoint> {
+        let index = self
+            .checkpoints
+            .iter()
+            .position(|&(id, _)| id == checkpoint.id)
+            .ok_or(InvalidCheckpoint)?;
+
+        let mark = self.checkpoints[index].1;
+        self.checkpoints.truncate(index);
+        Ok(mark)
+    }
+}
+
+impl<E, I: FallibleIterator<Error = LookaheadError<E>>, S: Storage<I::Item>> LookaheadBuffer<I, S> {
+    /// Sets the maximum number of items that can be looked ahead. Peeking further fails with
+    /// [LookaheadError::LimitExceeded] instead of pulling the items into memory.
+    ///
+    /// This is only available if the iterator fails with [LookaheadError], which can be
+    /// achieved with [Buffered::buffered_limited](crate::Buffered::buffered_limited).
+    #[inline]
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit.map(|limit| (limit, limit_exceeded as LimitFn<_>));
+    }
+
+    /// Runs `f` with the limit temporarily set to `limit`, restoring the previous limit afterward,
+    /// even if `f` panics.
+    pub fn with_limit<R>(&mut self, limit: usize, f: impl FnOnce(&mut Self) -> R) -> R {
+        let previous = self.limit;
+        self.set_limit(Some(limit));
+
+        let guard = LimitGuard {
+            buffer: self,
+            previous,
+        };
+
+        f(guard.buffer)
+    }
+}
+
+/// Restores the limit of a [LookaheadBuffer] when dropped.
+struct LimitGuard<'a, I: FallibleIterator, S> {
+    buffer: &'a mut LookaheadBuffer<I, S>,
+    previous: Option<(usize, LimitFn<I::Error>)>,
+}
+
+impl<I: FallibleIterator, S> Drop for LimitGuard<'_, I, S> {
+    #[inline]
+    fn drop(&mut self) {
+        self.buffer.limit = self.previous;
+    }
+}
+
+#[inline]
+fn limit_exceeded<E>(requested: usize, limit: usize) -> LookaheadError<E> {
+    LookaheadError::LimitExceeded { requested, limit }
+}
+
+impl<I: FallibleIterator, S: SliceStorage<I::Item>> LookaheadBuffer<I, S> {
+    /// Peeks into the next `n` items as one contiguous slice. The slice is shorter than `n`
+    /// only if the iterator ran out. This allows matching with slice patterns:
+    ///
+    /// ```
+    /// use fallible_iterator::IteratorExt;
+    /// use labuf::Buffered;
+    ///
+    /// let mut lab = ["x", ":", "u8"].into_iter().into_fallible().buffered();
+    /// assert!(matches!(lab.peek_slice(2), Ok([_, ":", ..])));
+    /// ```
+    pub fn peek_slice(&mut self, n: usize) -> Result<&[I::Item], I::Error> {
+        Ok(self.peek_slice_mut(n)?)
+    }
+
+    /// Peeks mutably into the next `n` items as one contiguous slice, like
+    /// [LookaheadBuffer::peek_slice].
+    pub fn peek_slice_mut(&mut self, n: usize) -> Result<&mut [I::Item], I::Error> {
+        self.try_ensure(n)?;
+
+        let slice = self.queue.make_contiguous();
+        let len = n.min(slice.len());
+        Ok(&mut slice[..len])
+    }
+
+    /// Peeks mutably into the items at the given lookahead `indices`, with 0 being the next item.
+    ///
+    /// Fails with [PeekManyError::OverlappingIndices] if an index appears twice and with
+    /// [PeekManyError::IndexOutOfBounds] if the iterator ends before an index is reached.
+    pub fn peek_many_mut<const N: usize>(
+        &mut self,
+        indices: [usize; N],
+    ) -> Result<[&mut I::Item; N], PeekManyError<I::Error>> {
+        if let Some(&max) = indices.iter().max() {
+            self.try_ensure(max.saturating_add(1))
+                .map_err(PeekManyError::Source)?;
+        }
+
+        self.queue
+            .make_contiguous()
+            .get_disjoint_mut(indices)
+            .map_err(|error| match error {
+                GetDisjointMutError::IndexOutOfBounds => PeekManyError::IndexOutOfBounds,
+                GetDisjointMutError::OverlappingIndices => PeekManyError::OverlappingIndices,
+            })
+    }
+}
+
+impl<I: FallibleIterator<Item: Clone>, S: Storage<I::Item>> LookaheadBuffer<I, S> {
+    /// Creates a [Checkpoint] at the current position. While it is live, consumed items are
+    /// retained so that [LookaheadBuffer::rewind] can yield them again.
+    ///
+    /// Checkpoints nest: an inner checkpoint can be rewound or committed independently
+    /// while the outer one stays live.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.clone_item = Some(I::Item::clone);
+
+        let id = self.next_checkpoint_id;
+        self.next_checkpoint_id = self.next_checkpoint_id.wrapping_add(1);
+        self.checkpoints.push((id, self.journal.len()));
+
+        Checkpoint { id }
+    }
+
+    /// Keeps the last `k` consumed items, which can be accessed with
+    /// [LookaheadBuffer::look_back] and [LookaheadBuffer::history].
+    #[inline]
+    #[must_use]
+    pub fn with_history(mut self, k: usize) -> Self {
+        self.clone_item = Some(I::Item::clone);
+        self.history_limit = k;
+
+        while self.history.len() > k {
+            self.history.pop_front();
+        }
+
+        self
+    }
+}
+
+impl<I: FallibleIterator, S: Storage<I::Item>> FallibleIterator for LookaheadBuffer<I, S> {
+    type Item = I::Item;
+    type Error = I::Error;
+
+    #[inline]
+    fn next(&mut self) -> Result<Option<I::Item>, I::Error> {
+        LookaheadBuffer::next(self)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.queue.len();
+
+        if self.exhausted {
+            return (len, Some(len));
+        }
+
+        let (lower, upper) = self.iter.size_hint();
+
+        (
+            lower.saturating_add(len),
+            upper.and_then(|upper| upper.checked_add(len)),
+        )
+    }
+
+    #[inline]
+    fn count(self) -> Result<usize, I::Error> {
+        if !self.can_delegate() {
+            return self.fold(0, |n, _| Ok(n + 1));
+        }
+
+        let len = self.queue.len();
+        Ok(len + self.iter.count()?)
+    }
+
+    fn nth(&mut self, n: usize) -> Result<Option<I::Item>, I::Error> {
+        if self.advance_by(n)? < n {
+            return Ok(None);
+        }
+
+        self.next()
+    }
+
+    fn try_fold<B, E, F>(&mut self, mut init: B, mut f: F) -> Result<B, E>
+    where
+        E: From<I::Error>,
+        F: FnMut(B, I::Item) -> Result<B, E>,
+    {
+        if !self.can_delegate() {
+            while let Some(token) = self.next()? {
+                init = f(init, token)?;
+            }
+
+            return Ok(init);
+        }
+
+        while let Some(token) = self.queue.pop_front() {
+            self.skip_positions(1);
+            init = f(init, token)?;
+        }
+
+        let position = &mut self.position;
+
+        let init = self.iter.try_fold(init, |init, token| {
+            position.advance(1);
+            f(init, token)
+        })?;
+
+        self.exhausted = true;
+        Ok(init)
+    }
+
+    fn fold<B, F>(mut self, mut init: B, mut f: F) -> Result<B, I::Error>
+    where
+        F: FnMut(B, I::Item) -> Result<B, I::Error>,
+    {
+        if !self.can_delegate() {
+            return self.try_fold(init, f);
+        }
+
+        while let Some(token) = self.queue.pop_front() {
+            init = f(init, token)?;
+        }
+
+        self.iter.fold(init, f)
+    }
+}
+
+impl<T: Clone, I: FallibleIterator<Item = T> + Clone, S: Storage<T> + Clone> Clone
+    for LookaheadBuffer<I, S>
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            iter: self.iter.clone(),
+            journal: self.journal.clone(),
+            checkpoints: self.checkpoints.clone(),
+            next_checkpoint_id: self.next_checkpoint_id,
+            clone_item: self.clone_item,
+            error: (self.error.as_ref())
+                .zip(self.clone_error)
+                .map(|(error, clone_error)| clone_error(error)),
+            clone_error: self.clone_error,
+            exhausted: self.exhausted,
+            position: self.position,
+            width: self.width,
+            history: self.history.clone(),
+            history_limit: self.history_limit,
+            limit: self.limit,
+        }
+    }
+}
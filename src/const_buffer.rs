@@ -0,0 +1,263 @@
+use crate::{CloneFn, LookaheadError, RingBuffer};
+use fallible_iterator::FallibleIterator;
+
+/// A lookahead-buffer like [LookaheadBuffer](crate::LookaheadBuffer), but backed by an inline
+/// [RingBuffer] of `K` items. It never allocates and thus works without the `alloc` feature.
+///
+/// Looking further than `K` items ahead fails with [LookaheadError::LimitExceeded].
+/// The last `H` consumed items are kept if enabled by [ConstLookaheadBuffer::with_history].
+pub struct ConstLookaheadBuffer<I: FallibleIterator, const K: usize, const H: usize = 0> {
+    iter: I,
+    queue: RingBuffer<I::Item, K>,
+
+    /// Whether the iterator has returned `Ok(None)`.
+    exhausted: bool,
+
+    /// The most recently consumed items, oldest first.
+    history: RingBuffer<I::Item, H>,
+
+    /// Clones consumed items into `history`. Set by [ConstLookaheadBuffer::with_history].
+    clone_item: Option<CloneFn<I::Item>>,
+}
+
+impl<I: FallibleIterator, const K: usize> ConstLookaheadBuffer<I, K> {
+    /// Create a new, empty [ConstLookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub const fn new(iter: I) -> Self {
+        Self {
+            iter,
+            queue: RingBuffer::new(),
+            exhausted: false,
+            history: RingBuffer::new(),
+            clone_item: None,
+        }
+    }
+
+    /// Keeps the last `H` consumed items, which can be accessed with
+    /// [ConstLookaheadBuffer::look_back] and [ConstLookaheadBuffer::history].
+    #[inline]
+    #[must_use]
+    pub fn with_history<const H: usize>(self) -> ConstLookaheadBuffer<I, K, H>
+    where
+        I::Item: Clone,
+    {
+        let Self {
+            iter,
+            queue,
+            exhausted,
+            ..
+        } = self;
+
+        ConstLookaheadBuffer {
+            iter,
+            queue,
+            exhausted,
+            history: RingBuffer::new(),
+            clone_item: Some(I::Item::clone),
+        }
+    }
+}
+
+impl<I: FallibleIterator, const K: usize, const H: usize> ConstLookaheadBuffer<I, K, H> {
+    /// Destructure `self` into the [FallibleIterator] and [RingBuffer].
+    #[inline]
+    #[must_use]
+    pub fn destructure(self) -> (I, RingBuffer<I::Item, K>) {
+        let Self { queue, iter, .. } = self;
+        (iter, queue)
+    }
+
+    /// Returns a reference to the queue.
+    #[inline]
+    #[must_use]
+    pub const fn queue(&self) -> &RingBuffer<I::Item, K> {
+        &self.queue
+    }
+
+    /// Returns a reference to the underlying iterator.
+    #[inline]
+    #[must_use]
+    pub const fn iter(&self) -> &I {
+        &self.iter
+    }
+
+    /// Returns a mutable reference to the underlying iterator.
+    #[inline]
+    #[must_use]
+    pub const fn iter_mut(&mut self) -> &mut I {
+        &mut self.iter
+    }
+
+    /// Returns `true` if the iterator has signaled its end by returning `Ok(None)`.
+    #[inline]
+    #[must_use]
+    pub const fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Tries to ensure that `n` items are in the queue. If, after a call to this function,
+    /// this is not the case, then this function could not pull any more items from the iterator.
+    /// Fails if `n > K`.
+    #[inline]
+    fn try_ensure(&mut self, n: usize) -> Result<(), LookaheadError<I::Error>> {
+        if n > K {
+            return Err(LookaheadError::LimitExceeded {
+                requested: n,
+                limit: K,
+            });
+        }
+
+        while self.queue.len() < n {
+            match self.pull()? {
+                // Cannot fail, because `self.queue.len() < n <= K`.
+                Some(token) => _ = self.queue.push_back(token),
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pulls the next item from the iterator, unless it is exhausted.
+    #[inline]
+    fn pull(&mut self) -> Result<Option<I::Item>, I::Error> {
+        if self.exhausted {
+            return Ok(None);
+        }
+
+        let token = self.iter.next()?;
+        self.exhausted = token.is_none();
+        Ok(token)
+    }
+
+    /// Peeks into the next `N` items. If less than `N` items will be yielded by the iterator
+    /// (or are already partially yielded into the queue), then the remaining slots in the
+    /// array will be [None].
+    pub fn peek_multiple<const N: usize>(
+        &mut self,
+    ) -> Result<[Option<&I::Item>; N], LookaheadError<I::Error>> {
+        self.try_ensure(N)?;
+        let mut pack = [None; N];
+
+        for (i, slot) in pack.iter_mut().enumerate() {
+            *slot = self.queue.get(i);
+        }
+
+        Ok(pack)
+    }
+
+    /// Peeks into the next item. Does not advance. Equivalent to `self.peek_n(0)`.
+    #[inline]
+    pub fn peek(&mut self) -> Result<Option<&I::Item>, LookaheadError<I::Error>> {
+        self.peek_n(0)
+    }
+
+    /// Peeks into the next item, mutably. Does not advance. Equivalent to `self.peek_n_mut(0)`.
+    #[inline]
+    pub fn peek_mut(&mut self) -> Result<Option<&mut I::Item>, LookaheadError<I::Error>> {
+        self.peek_n_mut(0)
+    }
+
+    /// Peeks into the nth item, with n=0 being the next item. Fails if `n >= K`.
+    #[inline]
+    pub fn peek_n(&mut self, n: usize) -> Result<Option<&I::Item>, LookaheadError<I::Error>> {
+        self.try_ensure(n.saturating_add(1))?;
+        Ok(self.queue.get(n))
+    }
+
+    /// Peeks into the nth item, mutably, with n=0 being the next item. Fails if `n >= K`.
+    #[inline]
+    pub fn peek_n_mut(
+        &mut self,
+        n: usize,
+    ) -> Result<Option<&mut I::Item>, LookaheadError<I::Error>> {
+        self.try_ensure(n.saturating_add(1))?;
+        Ok(self.queue.get_mut(n))
+    }
+
+    /// Consumes the next item, returning it.
+    ///
+    /// This is the same as [FallibleIterator::next], but does not require the trait to be in scope.
+    #[inline]
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<I::Item>, I::Error> {
+        let token = match self.queue.pop_front() {
+            Some(token) => token,
+            None => match self.pull()? {
+                Some(token) => token,
+                None => return Ok(None),
+            },
+        };
+
+        if let Some(clone_item) = self.clone_item {
+            if self.history.is_full() {
+                self.history.pop_front();
+            }
+
+            _ = self.history.push_back(clone_item(&token));
+        }
+
+        Ok(Some(token))
+    }
+
+    /// Returns the nth most recently consumed item, with n=0 being the last consumed item.
+    #[inline]
+    #[must_use]
+    pub fn look_back(&self, n: usize) -> Option<&I::Item> {
+        self.history
+            .len()
+            .checked_sub(n.saturating_add(1))
+            .and_then(|index| self.history.get(index))
+    }
+
+    /// Returns the most recently consumed items, oldest first.
+    #[inline]
+    #[must_use]
+    pub const fn history(&self) -> &RingBuffer<I::Item, H> {
+        &self.history
+    }
+}
+
+impl<I: FallibleIterator, const K: usize, const H: usize> FallibleIterator
+    for ConstLookaheadBuffer<I, K, H>
+{
+    type Item = I::Item;
+    type Error = I::Error;
+
+    #[inline]
+    fn next(&mut self) -> Result<Option<I::Item>, I::Error> {
+        ConstLookaheadBuffer::next(self)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.queue.len();
+
+        if self.exhausted {
+            return (len, Some(len));
+        }
+
+        let (lower, upper) = self.iter.size_hint();
+
+        (
+            lower.saturating_add(len),
+            upper.and_then(|upper| upper.checked_add(len)),
+        )
+    }
+}
+
+impl<T: Clone, I: FallibleIterator<Item = T> + Clone, const K: usize, const H: usize> Clone
+    for ConstLookaheadBuffer<I, K, H>
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+            queue: self.queue.clone(),
+            exhausted: self.exhausted,
+            history: self.history.clone(),
+            clone_item: self.clone_item,
+        }
+    }
+}
@@ -0,0 +1,37 @@
+#![cfg(test)]
+
+use super::*;
+use fallible_iterator::IteratorExt;
+
+#[test]
+fn const_buffer() {
+    let mut lab = [1, 2, 3, 4, 5]
+        .into_iter()
+        .into_fallible()
+        .buffered_const::<2>();
+    assert_eq!(lab.peek_n(1), Ok(Some(&2)));
+    assert_eq!(
+        lab.peek_n(2),
+        Err(LookaheadError::LimitExceeded {
+            requested: 3,
+            limit: 2
+        })
+    );
+    assert_eq!(
+        lab.peek_n(usize::MAX),
+        Err(LookaheadError::LimitExceeded {
+            requested: usize::MAX,
+            limit: 2
+        })
+    );
+    assert_eq!(lab.peek_multiple::<2>(), Ok([Some(&1), Some(&2)]));
+    assert_eq!(lab.next(), Ok(Some(1)));
+    assert_eq!(lab.next(), Ok(Some(2)));
+    assert_eq!(lab.next(), Ok(Some(3)));
+    assert_eq!(lab.peek_multiple::<2>(), Ok([Some(&4), Some(&5)]));
+    assert_eq!(lab.next(), Ok(Some(4)));
+    assert_eq!(lab.peek_multiple::<2>(), Ok([Some(&5), None]));
+    assert!(lab.is_exhausted());
+    assert_eq!(lab.next(), Ok(Some(5)));
+    assert_eq!(lab.next(), Ok(None));
+}
@@ -0,0 +1,100 @@
+use crate::{LookaheadBuffer, Storage};
+use alloc::collections::VecDeque;
+use fallible_iterator::FallibleIterator;
+
+/// A [LookaheadBuffer] that treats the end of the stream as an infinite sequence of a
+/// sentinel item, created by [LookaheadBuffer::with_eof].
+///
+/// Peeking never returns [None]: every position past the end holds the sentinel.
+pub struct EofBuffer<I: FallibleIterator, S = VecDeque<<I as FallibleIterator>::Item>> {
+    buffer: LookaheadBuffer<I, S>,
+    eof: I::Item,
+}
+
+impl<I: FallibleIterator, S: Storage<I::Item>> LookaheadBuffer<I, S> {
+    /// Wraps `self` in an [EofBuffer], which yields `eof` once the iterator ran out.
+    #[inline]
+    #[must_use]
+    pub const fn with_eof(self, eof: I::Item) -> EofBuffer<I, S> {
+        EofBuffer { buffer: self, eof }
+    }
+}
+
+impl<I: FallibleIterator, S: Storage<I::Item>> EofBuffer<I, S> {
+    /// Returns a reference to the underlying [LookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub const fn buffer(&self) -> &LookaheadBuffer<I, S> {
+        &self.buffer
+    }
+
+    /// Returns a mutable reference to the underlying [LookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub const fn buffer_mut(&mut self) -> &mut LookaheadBuffer<I, S> {
+        &mut self.buffer
+    }
+
+    /// Returns the sentinel item.
+    #[inline]
+    #[must_use]
+    pub const fn eof(&self) -> &I::Item {
+        &self.eof
+    }
+
+    /// Destructure `self` into the [LookaheadBuffer] and the sentinel item.
+    #[inline]
+    #[must_use]
+    pub fn into_parts(self) -> (LookaheadBuffer<I, S>, I::Item) {
+        (self.buffer, self.eof)
+    }
+
+    /// Returns `true` if there are no items left before the sentinel.
+    #[inline]
+    pub fn is_eof(&mut self) -> Result<bool, I::Error> {
+        Ok(self.buffer.peek()?.is_none())
+    }
+
+    /// Peeks into the next `N` items, filling the slots past the end with the sentinel.
+    pub fn peek_multiple<const N: usize>(&mut self) -> Result<[&I::Item; N], I::Error> {
+        let pack = self.buffer.peek_multiple::<N>()?;
+        Ok(pack.map(|token| token.unwrap_or(&self.eof)))
+    }
+
+    /// Peeks into the next item. Does not advance. Equivalent to `self.peek_n(0)`.
+    #[inline]
+    pub fn peek(&mut self) -> Result<&I::Item, I::Error> {
+        self.peek_n(0)
+    }
+
+    /// Peeks into the nth item, with n=0 being the next item. Returns the sentinel
+    /// if the iterator ends before.
+    #[inline]
+    pub fn peek_n(&mut self, n: usize) -> Result<&I::Item, I::Error> {
+        Ok(self.buffer.peek_n(n)?.unwrap_or(&self.eof))
+    }
+}
+
+impl<I: FallibleIterator<Item: Clone>, S: Storage<I::Item>> EofBuffer<I, S> {
+    /// Consumes the next item, returning it. Past the end, this returns a clone of the sentinel.
+    #[inline]
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<I::Item, I::Error> {
+        Ok(match self.buffer.next()? {
+            Some(token) => token,
+            None => self.eof.clone(),
+        })
+    }
+}
+
+impl<T: Clone, I: FallibleIterator<Item = T> + Clone, S: Storage<T> + Clone> Clone
+    for EofBuffer<I, S>
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+            eof: self.eof.clone(),
+        }
+    }
+}
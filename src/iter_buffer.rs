@@ -0,0 +1,143 @@
+use crate::LookaheadBuffer;
+use fallible_iterator::{Convert, FallibleIterator, IntoFallible, IteratorExt};
+
+/// Helper trait to add a function to [Iterator].
+/// Exposes [IteratorBuffered::buffered_iter] with default implementation.
+/// This trait is already implemented for all iterators.
+pub trait IteratorBuffered: Iterator + Sized {
+    #[inline]
+    fn buffered_iter(self) -> IterLookaheadBuffer<Self> {
+        IterLookaheadBuffer::new(self)
+    }
+
+    /// Wraps an iterator over [Result]s in a [LookaheadBuffer], which fails with the first
+    /// [Err] the iterator yields.
+    ///
+    /// To keep errors in the stream as items instead, for example to recover past them,
+    /// use [IteratorBuffered::buffered_iter].
+    #[inline]
+    fn buffered_results<T, E>(self) -> LookaheadBuffer<Convert<Self>>
+    where
+        Self: Iterator<Item = Result<T, E>>,
+    {
+        LookaheadBuffer::from_results(self)
+    }
+}
+
+impl<T: Iterator> IteratorBuffered for T {}
+
+/// A [LookaheadBuffer] over an infallible [Iterator]. Peeking returns items directly,
+/// instead of wrapped in a [Result].
+///
+/// The full API of [LookaheadBuffer] is available through [IterLookaheadBuffer::buffer_mut].
+pub struct IterLookaheadBuffer<I: Iterator> {
+    buffer: LookaheadBuffer<IntoFallible<I>>,
+}
+
+impl<I: Iterator> IterLookaheadBuffer<I> {
+    /// Create a new, empty [IterLookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub fn new(iter: I) -> Self {
+        Self {
+            buffer: LookaheadBuffer::new(iter.into_fallible()),
+        }
+    }
+
+    /// Returns a reference to the underlying [LookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub const fn buffer(&self) -> &LookaheadBuffer<IntoFallible<I>> {
+        &self.buffer
+    }
+
+    /// Returns a mutable reference to the underlying [LookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub const fn buffer_mut(&mut self) -> &mut LookaheadBuffer<IntoFallible<I>> {
+        &mut self.buffer
+    }
+
+    /// Returns the underlying [LookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub fn into_buffer(self) -> LookaheadBuffer<IntoFallible<I>> {
+        self.buffer
+    }
+
+    /// Peeks into the next `N` items. If less than `N` items will be yielded by the iterator
+    /// (or are already partially yielded into the queue), then the remaining slots in the
+    /// array will be [None].
+    #[inline]
+    pub fn peek_multiple<const N: usize>(&mut self) -> [Option<&I::Item>; N] {
+        let Ok(pack) = self.buffer.peek_multiple();
+        pack
+    }
+
+    /// Peeks into the next item. Does not advance. Equivalent to `self.peek_n(0)`.
+    #[inline]
+    pub fn peek(&mut self) -> Option<&I::Item> {
+        self.peek_n(0)
+    }
+
+    /// Peeks into the next item, mutably. Does not advance. Equivalent to `self.peek_n_mut(0)`.
+    #[inline]
+    pub fn peek_mut(&mut self) -> Option<&mut I::Item> {
+        self.peek_n_mut(0)
+    }
+
+    /// Peeks into the nth item, with n=0 being the next item.
+    #[inline]
+    pub fn peek_n(&mut self, n: usize) -> Option<&I::Item> {
+        let Ok(token) = self.buffer.peek_n(n);
+        token
+    }
+
+    /// Peeks into the nth item, mutably, with n=0 being the next item.
+    #[inline]
+    pub fn peek_n_mut(&mut self, n: usize) -> Option<&mut I::Item> {
+        let Ok(token) = self.buffer.peek_n_mut(n);
+        token
+    }
+
+    /// Consumes and returns the next item if `predicate` returns `true` for it.
+    #[inline]
+    pub fn next_if(&mut self, predicate: impl FnOnce(&I::Item) -> bool) -> Option<I::Item> {
+        let Ok(token) = self.buffer.next_if(predicate);
+        token
+    }
+}
+
+impl<T, E, I: Iterator<Item = Result<T, E>>> LookaheadBuffer<Convert<I>> {
+    /// Create a new, empty [LookaheadBuffer] over an iterator of [Result]s, like
+    /// [IteratorBuffered::buffered_results].
+    #[inline]
+    #[must_use]
+    pub fn from_results(iter: impl IntoIterator<IntoIter = I>) -> Self {
+        Self::new(fallible_iterator::convert(iter.into_iter()))
+    }
+}
+
+impl<I: Iterator> Iterator for IterLookaheadBuffer<I> {
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        let Ok(token) = self.buffer.next();
+        token
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.buffer.size_hint()
+    }
+}
+
+impl<T: Clone, I: Iterator<Item = T> + Clone> Clone for IterLookaheadBuffer<I> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+        }
+    }
+}
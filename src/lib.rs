@@ -6,14 +6,19 @@
 //! A lookahead-buffer implementation for [fallible_iterator]. Commonly needed and used for
 //! lexers and parsers.
 //!
-//! This crate is `no_std`, but uses `alloc`.
+//! This crate is `no_std`. [LookaheadBuffer] uses `alloc`, which can be turned off by disabling
+//! the default `alloc` feature. [ConstLookaheadBuffer] works without it.
 //!
 //! ## Usage Example
 //!
 //! ```
 //! use fallible_iterator::{FallibleIterator, IteratorExt};
+//! # #[cfg(feature = "alloc")]
 //! use labuf::{Buffered, LookaheadBuffer};
 //!
+//! # #[cfg(not(feature = "alloc"))]
+//! # fn main() {}
+//! # #[cfg(feature = "alloc")]
 //! fn main() {
 //!     let mut lab = [0, 1, 2, 3, 4].into_iter()
 //!         .into_fallible()
@@ -31,172 +36,144 @@
 //! }
 //! ```
 
+#[cfg(feature = "alloc")]
+mod buffer;
+mod const_buffer;
+mod const_tests;
+#[cfg(feature = "alloc")]
+mod eof;
+#[cfg(feature = "alloc")]
+mod iter_buffer;
+#[cfg(feature = "alloc")]
+mod recover;
+mod ring;
+#[cfg(feature = "alloc")]
+mod shared;
+#[cfg(feature = "alloc")]
+mod sources;
+mod storage;
 mod tests;
+#[cfg(feature = "alloc")]
+mod trivia;
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
-use alloc::collections::VecDeque;
+#[cfg(feature = "alloc")]
+pub use buffer::*;
+pub use const_buffer::*;
+#[cfg(feature = "alloc")]
+pub use eof::*;
+#[cfg(feature = "alloc")]
+pub use iter_buffer::*;
+#[cfg(feature = "alloc")]
+pub use recover::*;
+pub use ring::*;
+#[cfg(feature = "alloc")]
+pub use shared::*;
+#[cfg(feature = "alloc")]
+pub use sources::*;
+pub use storage::*;
+#[cfg(feature = "alloc")]
+pub use trivia::*;
+
+use core::fmt;
 use fallible_iterator::FallibleIterator;
 
 /// Helper trait to add a function to [FallibleIterator].
 /// Exposes [Buffered::buffered] with default implementation.
 /// This trait is already implemented for all fallible iterators.
 pub trait Buffered: FallibleIterator + Sized {
+    #[cfg(feature = "alloc")]
     #[inline]
     fn buffered(self) -> LookaheadBuffer<Self> {
         LookaheadBuffer::new(self)
     }
-}
-
-impl<T: FallibleIterator> Buffered for T {}
-
-/// A lookahead-buffer implementation for [fallible_iterator].
-/// Allows peeking into a [FallibleIterator].
-///
-/// Consumes the iterator lazily, only if the queue is empty and items are needed for peeking.
-pub struct LookaheadBuffer<I: FallibleIterator> {
-    iter: I,
-    queue: VecDeque<I::Item>,
-}
-
-impl<I: FallibleIterator> LookaheadBuffer<I> {
-    /// Create a new, empty [LookaheadBuffer].
-    #[inline]
-    #[must_use]
-    pub const fn new(iter: I) -> Self {
-        Self {
-            iter,
-            queue: VecDeque::new(),
-        }
-    }
-
-    /// Destructure `self` into the [FallibleIterator] and [VecDeque].
-    #[inline]
-    #[must_use]
-    pub fn destructure(self) -> (I, VecDeque<I::Item>) {
-        let Self { queue, iter } = self;
-        (iter, queue)
-    }
 
-    /// Returns a reference to the queue.
+    /// Wraps `self` in a [LookaheadBuffer] that can look at most `limit` items ahead.
+    /// Errors of `self` are wrapped in [LookaheadError::Source].
+    #[cfg(feature = "alloc")]
     #[inline]
-    #[must_use]
-    pub const fn queue(&self) -> &VecDeque<I::Item> {
-        &self.queue
+    fn buffered_limited(self, limit: usize) -> LimitedLookaheadBuffer<Self> {
+        let mut buffer = LookaheadBuffer::new(self.map_err(LookaheadError::Source as fn(_) -> _));
+        buffer.set_limit(Some(limit));
+        buffer
     }
 
-    /// Returns a mutable reference to the queue.
+    /// Wraps `self` in a [LookaheadBuffer] that does not stop at an error. Instead, `recover`
+    /// turns every error into an item, and the error is recorded as a [Diagnostic]. If
+    /// `recover` returns [None], the error is fatal and ends the stream.
+    #[cfg(feature = "alloc")]
     #[inline]
-    #[must_use]
-    pub const fn queue_mut(&mut self) -> &mut VecDeque<I::Item> {
-        &mut self.queue
+    fn buffered_recovering<F>(self, recover: F) -> LookaheadBuffer<Recover<Self, F>>
+    where
+        F: FnMut(&Self::Error) -> Option<Self::Item>,
+    {
+        LookaheadBuffer::new(Recover::new(self, recover))
     }
 
-    /// Returns a reference to the underlying iterator.
+    /// Wraps `self` in a [SharedLookaheadBuffer], which can be forked into multiple cursors.
+    #[cfg(feature = "alloc")]
     #[inline]
-    #[must_use]
-    pub const fn iter(&self) -> &I {
-        &self.iter
+    fn buffered_shared(self) -> SharedLookaheadBuffer<Self> {
+        SharedLookaheadBuffer::new(self)
     }
 
-    /// Returns a mutable reference to the underlying iterator.
+    /// Wraps `self` in a [ConstLookaheadBuffer] that can look at most `K` items ahead.
     #[inline]
-    #[must_use]
-    pub const fn iter_mut(&mut self) -> &mut I {
-        &mut self.iter
-    }
-
-    /// Tries to ensure that `n` items are in the queue. If, after a call to this function,
-    /// this is not the case, then this function could not pull any more items from the iterator.
-    #[inline]
-    fn try_ensure(&mut self, n: usize) -> Result<(), I::Error> {
-        for _ in 0..n.saturating_sub(self.queue.len()) {
-            if let Some(token) = self.iter.next()? {
-                self.queue.push_back(token);
-            } else {
-                break;
-            }
-        }
-
-        Ok(())
-    }
-
-    /// Peeks into the next `N` items. If less than `N` items will be yielded by the iterator
-    /// (or are already partially yielded into the queue), then the remaining slots in the
-    /// array will be [None].
-    pub fn peek_multiple<const N: usize>(&mut self) -> Result<[Option<&I::Item>; N], I::Error> {
-        self.try_ensure(N)?;
-        let mut pack = [None; N];
-
-        for i in 0..N {
-            pack[i] = self.queue.get(i);
-        }
-
-        Ok(pack)
-    }
-
-    /// Peeks into the next `N` items, mutably. If less than `N` items will be yielded by the iterator
-    /// (or are already partially yielded into the queue), then the remaining slots in the
-    /// array will be [None].
-    pub fn peek_multiple_mut<const N: usize>(
-        &mut self,
-    ) -> Result<[Option<&mut I::Item>; N], I::Error> {
-        self.try_ensure(N)?;
-
-        let mut pack = [const { None }; N];
-
-        let mut iter = pack.iter_mut();
-
-        for x in &mut self.queue {
-            // This shoud not generate a panic.
-            *iter.next().unwrap() = Some(x);
-        }
-
-        Ok(pack)
-    }
-
-    /// Peeks into the next item. Does not advance. Equivalent to `self.peek_n(0)`.
-    #[inline]
-    pub fn peek(&mut self) -> Result<Option<&I::Item>, I::Error> {
-        self.peek_n(0)
-    }
-
-    /// Peeks into the next item, mutably. Does not advance. Equivalent to `self.peek_n_mut(0)`.
-    #[inline]
-    pub fn peek_mut(&mut self) -> Result<Option<&mut I::Item>, I::Error> {
-        self.peek_n_mut(0)
+    fn buffered_const<const K: usize>(self) -> ConstLookaheadBuffer<Self, K> {
+        ConstLookaheadBuffer::new(self)
     }
+}
 
-    /// Peeks into the nth item, with n=0 being the next item.
-    #[inline]
-    pub fn peek_n(&mut self, n: usize) -> Result<Option<&I::Item>, I::Error> {
-        self.try_ensure(n + 1)?;
-        Ok(self.queue.get(n))
-    }
+impl<T: FallibleIterator> Buffered for T {}
 
-    /// Peeks into the nth item, mutably, with n=0 being the next item.
-    #[inline]
-    pub fn peek_n_mut(&mut self, n: usize) -> Result<Option<&mut I::Item>, I::Error> {
-        self.try_ensure(n + 1)?;
-        Ok(self.queue.get_mut(n))
-    }
+/// A [LookaheadBuffer] whose iterator errors are wrapped in [LookaheadError], as returned by
+/// [Buffered::buffered_limited].
+#[cfg(feature = "alloc")]
+pub type LimitedLookaheadBuffer<I> = LookaheadBuffer<
+    fallible_iterator::MapErr<
+        I,
+        fn(<I as FallibleIterator>::Error) -> LookaheadError<<I as FallibleIterator>::Error>,
+    >,
+>;
+
+/// A type-erased [Clone::clone], so that only the methods that need it require `T: Clone`.
+type CloneFn<T> = fn(&T) -> T;
+
+/// An error that occurred while looking ahead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LookaheadError<E> {
+    /// The iterator failed.
+    Source(E),
+
+    /// More items were requested than the buffer can hold.
+    LimitExceeded {
+        /// The number of items that would have to be buffered.
+        requested: usize,
+
+        /// The maximum number of items that can be buffered.
+        limit: usize,
+    },
+}
 
-    /// Consumes the next item, returning it.
+impl<E> From<E> for LookaheadError<E> {
     #[inline]
-    pub fn next(&mut self) -> Result<Option<I::Item>, I::Error> {
-        match self.queue.pop_front() {
-            Some(token) => Ok(Some(token)),
-            None => self.iter.next(),
-        }
+    fn from(error: E) -> Self {
+        Self::Source(error)
     }
 }
 
-impl<T: Clone, I: FallibleIterator<Item = T> + Clone> Clone for LookaheadBuffer<I> {
-    #[inline]
-    fn clone(&self) -> Self {
-        Self {
-            queue: self.queue.clone(),
-            iter: self.iter.clone(),
+impl<E: fmt::Display> fmt::Display for LookaheadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Source(error) => error.fmt(f),
+            Self::LimitExceeded { requested, limit } => write!(
+                f,
+                "lookahead too deep: {requested} items requested, but the limit is {limit}"
+            ),
         }
     }
 }
+
+impl<E: core::error::Error> core::error::Error for LookaheadError<E> {}
@@ -0,0 +1,140 @@
+use crate::{LookaheadBuffer, Storage};
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use fallible_iterator::FallibleIterator;
+
+/// A [FallibleIterator] adapter that turns errors into items, so that a
+/// [LookaheadBuffer] over it never stops at an error. Created by
+/// [Buffered::buffered_recovering](crate::Buffered::buffered_recovering).
+///
+/// Every error is passed to a user function that creates the item to yield in its place
+/// and is then recorded as a [Diagnostic]. If the function returns [None], the error is
+/// fatal: it is still recorded, but the iterator ends and the underlying iterator is not
+/// polled again. Otherwise, the iterator ends when the underlying iterator returns `Ok(None)`.
+#[derive(Debug, Clone)]
+pub struct Recover<I: FallibleIterator, F> {
+    iter: I,
+    recover: F,
+    diagnostics: Vec<Diagnostic<I::Error>>,
+
+    /// The number of items yielded so far.
+    index: usize,
+
+    /// Whether a fatal error ended the iterator.
+    aborted: bool,
+}
+
+/// An error that was turned into an item by [Recover].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic<E> {
+    /// The index of the item that replaced the error, counting all items yielded before it.
+    pub index: usize,
+
+    /// The error.
+    pub error: E,
+}
+
+impl<I: FallibleIterator, F: FnMut(&I::Error) -> Option<I::Item>> Recover<I, F> {
+    /// Wraps `iter`, turning its errors into items with `recover`.
+    #[inline]
+    #[must_use]
+    pub const fn new(iter: I, recover: F) -> Self {
+        Self {
+            iter,
+            recover,
+            diagnostics: Vec::new(),
+            index: 0,
+            aborted: false,
+        }
+    }
+
+    /// Returns the errors recorded so far, oldest first.
+    #[inline]
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic<I::Error>] {
+        &self.diagnostics
+    }
+
+    /// Removes and returns the errors recorded so far, oldest first.
+    #[inline]
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic<I::Error>> {
+        core::mem::take(&mut self.diagnostics)
+    }
+
+    /// Returns `true` if a fatal error ended the iterator. It is the last recorded [Diagnostic].
+    #[inline]
+    #[must_use]
+    pub const fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+}
+
+impl<I: FallibleIterator, F: FnMut(&I::Error) -> Option<I::Item>> FallibleIterator
+    for Recover<I, F>
+{
+    type Item = I::Item;
+    type Error = Infallible;
+
+    fn next(&mut self) -> Result<Option<I::Item>, Infallible> {
+        if self.aborted {
+            return Ok(None);
+        }
+
+        let token = match self.iter.next() {
+            Ok(None) => return Ok(None),
+            Ok(Some(token)) => token,
+            Err(error) => {
+                let token = (self.recover)(&error);
+
+                self.diagnostics.push(Diagnostic {
+                    index: self.index,
+                    error,
+                });
+
+                match token {
+                    Some(token) => token,
+                    None => {
+                        self.aborted = true;
+                        return Ok(None);
+                    }
+                }
+            }
+        };
+
+        self.index += 1;
+        Ok(Some(token))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.aborted {
+            return (0, Some(0));
+        }
+
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<I: FallibleIterator, F: FnMut(&I::Error) -> Option<I::Item>, S: Storage<I::Item>>
+    LookaheadBuffer<Recover<I, F>, S>
+{
+    /// Returns the errors that were turned into items so far, oldest first.
+    #[inline]
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic<I::Error>] {
+        self.iter().diagnostics()
+    }
+
+    /// Removes and returns the errors that were turned into items so far, oldest first.
+    #[inline]
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic<I::Error>> {
+        self.iter_mut().take_diagnostics()
+    }
+
+    /// Returns `true` if a fatal error ended the iterator.
+    #[inline]
+    #[must_use]
+    pub const fn is_aborted(&self) -> bool {
+        self.iter().is_aborted()
+    }
+}
@@ -0,0 +1,174 @@
+/// A fixed-capacity FIFO queue that stores up to `N` items inline, without allocating.
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T, const N: usize> {
+    slots: [Option<T>; N],
+
+    /// The index of the front item in `slots`.
+    head: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    /// Creates a new, empty [RingBuffer].
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { None }; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of items in the buffer.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer contains no items.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if the buffer contains `N` items.
+    #[inline]
+    #[must_use]
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Returns the maximum number of items, `N`.
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    const fn slot(&self, index: usize) -> usize {
+        (self.head + index) % N
+    }
+
+    /// Appends an item to the back. If the buffer is full, the item is returned as the error.
+    #[inline]
+    pub fn push_back(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+
+        self.slots[self.slot(self.len)] = Some(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Prepends an item to the front. If the buffer is full, the item is returned as the error.
+    #[inline]
+    pub fn push_front(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+
+        self.head = self.slot(N - 1);
+        self.slots[self.head] = Some(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Inserts an item at `index`, with 0 being the front, shifting all items after it back.
+    /// If the buffer is full, the item is returned as the error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of items.
+    pub fn insert(&mut self, index: usize, item: T) -> Result<(), T> {
+        assert!(index <= self.len, "insertion index out of bounds");
+
+        if self.is_full() {
+            return Err(item);
+        }
+
+        for i in (index..self.len).rev() {
+            self.slots[self.slot(i + 1)] = self.slots[self.slot(i)].take();
+        }
+
+        self.slots[self.slot(index)] = Some(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes and returns the item at `index`, with 0 being the front, shifting all items
+    /// after it forward.
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.len {
+            return None;
+        }
+
+        let item = self.slots[self.slot(index)].take();
+
+        for i in index + 1..self.len {
+            self.slots[self.slot(i - 1)] = self.slots[self.slot(i)].take();
+        }
+
+        self.len -= 1;
+        item
+    }
+
+    /// Removes and returns the front item.
+    #[inline]
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let item = self.slots[self.head].take();
+        self.head = self.slot(1);
+        self.len -= 1;
+        item
+    }
+
+    /// Returns a reference to the item at `index`, with 0 being the front.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+
+        self.slots[self.slot(index)].as_ref()
+    }
+
+    /// Returns a mutable reference to the item at `index`, with 0 being the front.
+    #[inline]
+    #[must_use]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+
+        self.slots[self.slot(index)].as_mut()
+    }
+
+    /// Removes all items.
+    #[inline]
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+        self.head = 0;
+    }
+
+    /// Returns an iterator over the items, front to back.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len).filter_map(|index| self.get(index))
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{LookaheadBuffer, Storage};
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use fallible_iterator::FallibleIterator;
+
+/// A cursor into a lookahead-buffer that is shared with other cursors, created by
+/// [SharedLookaheadBuffer::fork].
+///
+/// Every cursor has its own read position. Items are pulled from the iterator once and
+/// dropped only after every live cursor has moved past them. Errors stay at their position
+/// in the stream and are returned to every cursor that reaches them. Cursors return clones
+/// of items and errors, so that no cursor borrows from the shared buffer; wrap them in an
+/// [Rc] if they are expensive to clone.
+pub struct SharedLookaheadBuffer<I: FallibleIterator> {
+    shared: Rc<Shared<I>>,
+
+    /// The index of this cursor in [Shared::cursors].
+    id: usize,
+}
+
+struct Shared<I: FallibleIterator> {
+    buffer: RefCell<SharedQueue<I>>,
+
+    /// The read position of each cursor, or [None] if it was dropped.
+    cursors: RefCell<Vec<Option<usize>>>,
+}
+
+struct SharedQueue<I: FallibleIterator> {
+    iter: I,
+    queue: VecDeque<I::Item>,
+
+    /// The index of the front item of `queue` in the stream.
+    base: usize,
+
+    /// The error the iterator failed with, located right after the last queued item.
+    error: Option<I::Error>,
+
+    /// Whether the iterator has returned `Ok(None)`.
+    exhausted: bool,
+}
+
+impl<I: FallibleIterator<Error: Clone>> SharedQueue<I> {
+    /// Tries to pull items until the item at stream index `index` is queued.
+    fn try_ensure(&mut self, index: usize) -> Result<(), I::Error> {
+        while self.base + self.queue.len() <= index {
+            if let Some(error) = &self.error {
+                return Err(error.clone());
+            }
+
+            if self.exhausted {
+                break;
+            }
+
+            match self.iter.next() {
+                Ok(Some(token)) => self.queue.push_back(token),
+                Ok(None) => self.exhausted = true,
+                Err(error) => return Err(self.error.insert(error).clone()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<I: FallibleIterator> SharedQueue<I> {
+    /// Drops all items before stream index `min`.
+    #[inline]
+    fn trim(&mut self, min: usize) {
+        while self.base < min && self.queue.pop_front().is_some() {
+            self.base += 1;
+        }
+    }
+}
+
+impl<I: FallibleIterator> SharedLookaheadBuffer<I> {
+    /// Create a new, empty [SharedLookaheadBuffer] with a single cursor.
+    #[inline]
+    #[must_use]
+    pub fn new(iter: I) -> Self {
+        Self::from_queue(iter, VecDeque::new())
+    }
+
+    /// Create a new [SharedLookaheadBuffer] with a single cursor, whose first items are `queue`.
+    pub(crate) fn from_queue(iter: I, queue: VecDeque<I::Item>) -> Self {
+        let buffer = SharedQueue {
+            iter,
+            queue,
+            base: 0,
+            error: None,
+            exhausted: false,
+        };
+
+        let shared = Shared {
+            buffer: RefCell::new(buffer),
+            cursors: RefCell::new(alloc::vec![Some(0)]),
+        };
+
+        Self {
+            shared: Rc::new(shared),
+            id: 0,
+        }
+    }
+
+    /// Creates another cursor at the same position.
+    pub fn fork(&self) -> Self {
+        let mut cursors = self.shared.cursors.borrow_mut();
+        let position = cursors[self.id];
+
+        let id = match cursors.iter().position(Option::is_none) {
+            Some(id) => {
+                cursors[id] = position;
+                id
+            }
+            None => {
+                cursors.push(position);
+                cursors.len() - 1
+            }
+        };
+
+        Self {
+            shared: self.shared.clone(),
+            id,
+        }
+    }
+
+    /// Returns the number of items this cursor has consumed.
+    #[inline]
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.shared.cursors.borrow()[self.id].unwrap_or_default()
+    }
+
+    /// Returns the number of live cursors.
+    #[inline]
+    #[must_use]
+    pub fn cursors(&self) -> usize {
+        self.shared.cursors.borrow().iter().flatten().count()
+    }
+
+    /// Drops all items that every live cursor has moved past.
+    fn trim(&self) {
+        let min = self.shared.cursors.borrow().iter().flatten().min().copied();
+
+        if let Some(min) = min {
+            self.shared.buffer.borrow_mut().trim(min);
+        }
+    }
+}
+
+impl<I: FallibleIterator<Error: Clone>, S: Storage<I::Item>> LookaheadBuffer<I, S> {
+    /// Splits `self` into two [SharedLookaheadBuffer] cursors, both starting at the next item.
+    ///
+    /// Already buffered items are kept, as is a sticky error or exhaustion. Items consumed
+    /// through one cursor stay buffered for the other, and an error from the iterator is
+    /// returned to both at the same position. If `I::Error` is not [Clone], map it into an
+    /// [Rc] before buffering.
+    ///
+    /// The cursors only count items from here on: the position, widths and history are
+    /// not carried over. Since checkpoints cannot be carried over either, `self` is returned
+    /// unchanged as the error while any checkpoint is live.
+    #[allow(clippy::result_large_err)]
+    pub fn tee(self) -> Result<(SharedLookaheadBuffer<I>, SharedLookaheadBuffer<I>), Self> {
+        if self.checkpoints() > 0 {
+            return Err(self);
+        }
+
+        let error = self.error().cloned();
+        let exhausted = self.is_exhausted();
+        let (iter, mut storage) = self.destructure();
+
+        let mut queue = VecDeque::with_capacity(storage.len());
+        while let Some(token) = storage.pop_front() {
+            queue.push_back(token);
+        }
+
+        let first = SharedLookaheadBuffer::from_queue(iter, queue);
+        {
+            let mut buffer = first.shared.buffer.borrow_mut();
+            buffer.error = error;
+            buffer.exhausted = exhausted;
+        }
+
+        let second = first.fork();
+        Ok((first, second))
+    }
+}
+
+impl<I: FallibleIterator<Item: Clone, Error: Clone>> SharedLookaheadBuffer<I> {
+    /// Returns a clone of the item at stream index `index`, pulling items as needed.
+    fn get(&self, index: usize) -> Result<Option<I::Item>, I::Error> {
+        let mut buffer = self.shared.buffer.borrow_mut();
+        buffer.try_ensure(index)?;
+        Ok(buffer.queue.get(index - buffer.base).cloned())
+    }
+
+    /// Peeks into the next item of this cursor, returning a clone of it. Does not advance.
+    /// Equivalent to `self.peek_n(0)`.
+    #[inline]
+    pub fn peek(&self) -> Result<Option<I::Item>, I::Error> {
+        self.peek_n(0)
+    }
+
+    /// Peeks into the nth item of this cursor, with n=0 being the next item, returning a
+    /// clone of it.
+    #[inline]
+    pub fn peek_n(&self, n: usize) -> Result<Option<I::Item>, I::Error> {
+        self.get(self.index().saturating_add(n))
+    }
+
+    /// Consumes the next item of this cursor, returning a clone of it.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<I::Item>, I::Error> {
+        let index = self.index();
+        let token = self.get(index)?;
+
+        if token.is_some() {
+            self.shared.cursors.borrow_mut()[self.id] = Some(index + 1);
+            self.trim();
+        }
+
+        Ok(token)
+    }
+}
+
+impl<I: FallibleIterator<Item: Clone, Error: Clone>> FallibleIterator for SharedLookaheadBuffer<I> {
+    type Item = I::Item;
+    type Error = I::Error;
+
+    #[inline]
+    fn next(&mut self) -> Result<Option<I::Item>, I::Error> {
+        SharedLookaheadBuffer::next(self)
+    }
+}
+
+impl<I: FallibleIterator> Drop for SharedLookaheadBuffer<I> {
+    fn drop(&mut self) {
+        self.shared.cursors.borrow_mut()[self.id] = None;
+        self.trim();
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{LookaheadBuffer, Storage};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
+use fallible_iterator::FallibleIterator;
+
+/// A [FallibleIterator] over a stack of sources, like include files or macro expansions.
+///
+/// Items are yielded from the source on top of the stack. Once it ends, it is popped and
+/// the source below continues. Buffer it and use [LookaheadBuffer::push_source] to push a
+/// source at the current position; peeking crosses source boundaries transparently.
+pub struct SourceStack<I: FallibleIterator> {
+    frames: Vec<Frame<I, I::Item>>,
+
+    /// The id of the next pushed source.
+    next_source: usize,
+
+    /// The maximum number of sources on the stack.
+    depth_limit: Option<usize>,
+}
+
+#[derive(Clone)]
+struct Frame<I, T> {
+    iter: I,
+    source: usize,
+
+    /// Whether `iter` has returned `Ok(None)`.
+    done: bool,
+
+    /// Items that were already pulled from the sources below, when this source was pushed.
+    /// They are yielded after `iter` ends.
+    resume: VecDeque<Sourced<T>>,
+}
+
+/// An item of a [SourceStack], tagged with the source it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sourced<T> {
+    /// The id of the source, with 0 being the root source. Ids are given out in the order
+    /// the sources were pushed.
+    pub source: usize,
+
+    /// The item.
+    pub item: T,
+}
+
+/// A source was pushed onto a [SourceStack] that is already at its depth limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthLimitExceeded {
+    /// The maximum number of sources on the stack.
+    pub limit: usize,
+}
+
+impl fmt::Display for DepthLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sources nested too deep: the limit is {}", self.limit)
+    }
+}
+
+impl core::error::Error for DepthLimitExceeded {}
+
+/// The error returned by [LookaheadBuffer::push_source].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushSourceError<E> {
+    /// The stack is already at its depth limit.
+    DepthLimitExceeded(DepthLimitExceeded),
+
+    /// A [Checkpoint](crate::Checkpoint) is live. Rewinding to it could not undo the push.
+    CheckpointLive,
+
+    /// The buffer stores a sticky error, which belongs before the new source.
+    StoredError,
+
+    /// The iterator failed while checking whether the top source has ended.
+    Source(E),
+}
+
+impl<E> From<DepthLimitExceeded> for PushSourceError<E> {
+    #[inline]
+    fn from(error: DepthLimitExceeded) -> Self {
+        Self::DepthLimitExceeded(error)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for PushSourceError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DepthLimitExceeded(error) => error.fmt(f),
+            Self::CheckpointLive => f.write_str("cannot push a source while a checkpoint is live"),
+            Self::StoredError => f.write_str("cannot push a source after a stored error"),
+            Self::Source(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: core::error::Error> core::error::Error for PushSourceError<E> {}
+
+impl<I: FallibleIterator> SourceStack<I> {
+    /// Create a new [SourceStack] with `root` as source 0.
+    #[inline]
+    #[must_use]
+    pub fn new(root: I) -> Self {
+        Self {
+            frames: alloc::vec![Frame {
+                iter: root,
+                source: 0,
+                done: false,
+                resume: VecDeque::new(),
+            }],
+            next_source: 1,
+            depth_limit: None,
+        }
+    }
+
+    /// Limits the number of sources on the stack, including the root, to `limit`.
+    #[inline]
+    #[must_use]
+    pub fn with_depth_limit(mut self, limit: usize) -> Self {
+        self.depth_limit = Some(limit);
+        self
+    }
+
+    /// Returns the depth limit.
+    #[inline]
+    #[must_use]
+    pub const fn depth_limit(&self) -> Option<usize> {
+        self.depth_limit
+    }
+
+    /// Returns the number of sources on the stack, not counting sources that are known to
+    /// have ended.
+    #[inline]
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        let ended = self
+            .frames
+            .iter()
+            .rev()
+            .take_while(|frame| frame.done && frame.resume.is_empty())
+            .count();
+
+        self.frames.len() - ended
+    }
+
+    /// Pushes `iter` on top of the stack, returning its source id.
+    #[inline]
+    pub fn push(&mut self, iter: I) -> Result<usize, DepthLimitExceeded> {
+        self.push_with(iter, VecDeque::new())
+    }
+
+    /// Fails if another source would exceed the depth limit.
+    fn check_depth(&self) -> Result<(), DepthLimitExceeded> {
+        match self.depth_limit {
+            Some(limit) if self.depth() >= limit => Err(DepthLimitExceeded { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Pushes `iter` on top of the stack, followed by `resume`.
+    fn push_with(
+        &mut self,
+        iter: I,
+        resume: VecDeque<Sourced<I::Item>>,
+    ) -> Result<usize, DepthLimitExceeded> {
+        self.check_depth()?;
+        self.frames.truncate(self.depth());
+
+        let source = self.next_source;
+        self.next_source += 1;
+
+        self.frames.push(Frame {
+            iter,
+            source,
+            done: false,
+            resume,
+        });
+
+        Ok(source)
+    }
+}
+
+impl<I: FallibleIterator> FallibleIterator for SourceStack<I> {
+    type Item = Sourced<I::Item>;
+    type Error = I::Error;
+
+    fn next(&mut self) -> Result<Option<Sourced<I::Item>>, I::Error> {
+        while let Some(top) = self.frames.last_mut() {
+            if !top.done {
+                match top.iter.next()? {
+                    Some(item) => {
+                        return Ok(Some(Sourced {
+                            source: top.source,
+                            item,
+                        }));
+                    }
+                    None => top.done = true,
+                }
+            }
+
+            if let Some(token) = top.resume.pop_front() {
+                return Ok(Some(token));
+            }
+
+            self.frames.pop();
+        }
+
+        Ok(None)
+    }
+}
+
+impl<T: Clone, I: FallibleIterator<Item = T> + Clone> Clone for SourceStack<I> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            frames: self.frames.clone(),
+            next_source: self.next_source,
+            depth_limit: self.depth_limit,
+        }
+    }
+}
+
+impl<I: FallibleIterator, S: Storage<Sourced<I::Item>>> LookaheadBuffer<SourceStack<I>, S> {
+    /// Pushes `iter` at the current position, returning its source id. Already buffered
+    /// items are yielded after it ends.
+    ///
+    /// Fails if a checkpoint is live or a sticky error is stored, since the pushed source
+    /// would be out of order with them. Commit the checkpoints or handle the error first.
+    ///
+    /// At the depth limit, one more item is pulled first, since the top source may have
+    /// ended without being polled again.
+    pub fn push_source(&mut self, iter: I) -> Result<usize, PushSourceError<I::Error>> {
+        if self.checkpoints() > 0 {
+            return Err(PushSourceError::CheckpointLive);
+        }
+
+        if self.error().is_some() {
+            return Err(PushSourceError::StoredError);
+        }
+
+        if self.iter().check_depth().is_err() {
+            let buffered = self.queue().len();
+            self.peek_n(buffered).map_err(PushSourceError::Source)?;
+        }
+
+        self.iter().check_depth()?;
+
+        let queue = self.queue_mut();
+        let mut resume = VecDeque::with_capacity(queue.len());
+        while let Some(token) = queue.pop_front() {
+            resume.push_back(token);
+        }
+
+        self.reset_exhaustion();
+        Ok(self.iter_mut().push_with(iter, resume)?)
+    }
+
+    /// Returns the number of sources on the stack.
+    #[inline]
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.iter().depth()
+    }
+}
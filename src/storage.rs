@@ -0,0 +1,194 @@
+use crate::RingBuffer;
+#[cfg(feature = "alloc")]
+use alloc::collections::VecDeque;
+
+/// The queue of a [LookaheadBuffer](crate::LookaheadBuffer).
+///
+/// Implement this to back the buffer with something other than a [VecDeque], like inline or
+/// arena-backed storage. Items pulled from the iterator are appended with [Storage::push_back].
+/// A lookahead limit bounds how many items are pulled, but the queue can still grow past it:
+/// [Storage::push_front] and [Storage::insert] are called when rewinding to a checkpoint,
+/// unreading items or splicing.
+///
+/// A bounded storage reports its size with [Storage::max_len]. The buffer never pulls more
+/// items than that: peeking further yields [None], or fails with
+/// [LookaheadError::LimitExceeded](crate::LookaheadError::LimitExceeded) if a limit is set.
+/// Since the other methods cannot fail, [RingBuffer] panics if rewinding or unreading
+/// grows it past its capacity.
+pub trait Storage<T> {
+    /// Appends an item to the back.
+    fn push_back(&mut self, item: T);
+
+    /// Prepends an item to the front.
+    fn push_front(&mut self, item: T);
+
+    /// Removes and returns the front item.
+    fn pop_front(&mut self) -> Option<T>;
+
+    /// Inserts an item at `index`, with 0 being the front, shifting all items after it back.
+    fn insert(&mut self, index: usize, item: T);
+
+    /// Removes and returns the item at `index`, with 0 being the front, shifting all items
+    /// after it forward.
+    fn remove(&mut self, index: usize) -> Option<T>;
+
+    /// Returns a reference to the item at `index`, with 0 being the front.
+    fn get(&self, index: usize) -> Option<&T>;
+
+    /// Returns a mutable reference to the item at `index`, with 0 being the front.
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>;
+
+    /// Returns the number of items.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if there are no items.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all items.
+    fn clear(&mut self);
+    /// Returns the maximum number of items, or [None] if the storage is unbounded.
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// A [Storage] whose items can be viewed as slices.
+pub trait SliceStorage<T>: Storage<T> {
+    /// Returns the items as two slices, front to back.
+    fn as_slices(&self) -> (&[T], &[T]);
+
+    /// Returns the items as two mutable slices, front to back.
+    fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]);
+
+    /// Rearranges the items so that they are contiguous and returns them as one slice.
+    fn make_contiguous(&mut self) -> &mut [T];
+}
+
+/// Panics in [Storage::push_back], [Storage::push_front] and [Storage::insert] if the buffer is full.
+impl<T, const N: usize> Storage<T> for RingBuffer<T, N> {
+    #[inline]
+    fn push_back(&mut self, item: T) {
+        if RingBuffer::push_back(self, item).is_err() {
+            panic!("ring buffer is full");
+        }
+    }
+
+    #[inline]
+    fn push_front(&mut self, item: T) {
+        if RingBuffer::push_front(self, item).is_err() {
+            panic!("ring buffer is full");
+        }
+    }
+
+    #[inline]
+    fn pop_front(&mut self) -> Option<T> {
+        RingBuffer::pop_front(self)
+    }
+
+    #[inline]
+    fn insert(&mut self, index: usize, item: T) {
+        if RingBuffer::insert(self, index, item).is_err() {
+            panic!("ring buffer is full");
+        }
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) -> Option<T> {
+        RingBuffer::remove(self, index)
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> Option<&T> {
+        RingBuffer::get(self, index)
+    }
+
+    #[inline]
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        RingBuffer::get_mut(self, index)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        RingBuffer::len(self)
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        RingBuffer::clear(self);
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        Some(N)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Storage<T> for VecDeque<T> {
+    #[inline]
+    fn push_back(&mut self, item: T) {
+        VecDeque::push_back(self, item);
+    }
+
+    #[inline]
+    fn push_front(&mut self, item: T) {
+        VecDeque::push_front(self, item);
+    }
+
+    #[inline]
+    fn pop_front(&mut self) -> Option<T> {
+        VecDeque::pop_front(self)
+    }
+
+    #[inline]
+    fn insert(&mut self, index: usize, item: T) {
+        VecDeque::insert(self, index, item);
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) -> Option<T> {
+        VecDeque::remove(self, index)
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> Option<&T> {
+        VecDeque::get(self, index)
+    }
+
+    #[inline]
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        VecDeque::get_mut(self, index)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        VecDeque::clear(self);
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> SliceStorage<T> for VecDeque<T> {
+    #[inline]
+    fn as_slices(&self) -> (&[T], &[T]) {
+        VecDeque::as_slices(self)
+    }
+
+    #[inline]
+    fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+        VecDeque::as_mut_slices(self)
+    }
+
+    #[inline]
+    fn make_contiguous(&mut self) -> &mut [T] {
+        VecDeque::make_contiguous(self)
+    }
+}
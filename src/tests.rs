@@ -1,6 +1,8 @@
-#![cfg(test)]
+#![cfg(all(test, feature = "alloc"))]
 
 use super::*;
+use alloc::vec;
+use alloc::vec::Vec;
 use fallible_iterator::IteratorExt;
 
 #[test]
@@ -38,3 +40,918 @@ fn next() {
     assert_eq!(lab.next(), Ok(Some(5)));
     assert_eq!(lab.next(), Ok(None));
 }
+
+#[test]
+fn fallible_iterator() {
+    let mut lab = [1, 2, 3, 4, 5].into_iter().into_fallible().buffered();
+    assert_eq!(lab.peek_n(2), Ok(Some(&3)));
+    assert_eq!(lab.size_hint(), (5, Some(5)));
+
+    let doubled = lab.by_ref().take(2).map(|x| Ok(x * 2)).collect::<Vec<_>>();
+    assert_eq!(doubled, Ok(vec![2, 4]));
+    assert_eq!(lab.peek(), Ok(Some(&3)));
+    assert_eq!(lab.count(), Ok(3));
+
+    let lab = [1, 2, 3, 4, 5].into_iter().into_fallible().buffered();
+    let mut stacked = lab.buffered();
+    assert_eq!(stacked.peek_n(1), Ok(Some(&2)));
+    assert_eq!(stacked.fold(0, |acc, x| Ok(acc + x)), Ok(15));
+}
+
+#[test]
+fn nth_and_advance_by() {
+    let mut lab = [1, 2, 3, 4, 5, 6, 7].into_iter().into_fallible().buffered();
+    assert_eq!(lab.peek_n(2), Ok(Some(&3)));
+    assert_eq!(FallibleIterator::nth(&mut lab, 1), Ok(Some(2)));
+    assert_eq!(FallibleIterator::nth(&mut lab, 2), Ok(Some(5)));
+    assert_eq!(lab.advance_by(1), Ok(1));
+    assert_eq!(lab.advance_by(3), Ok(1));
+    assert_eq!(lab.next(), Ok(None));
+}
+
+#[test]
+fn checkpoint_rewind() {
+    let mut lab = [1, 2, 3, 4, 5].into_iter().into_fallible().buffered();
+    assert_eq!(lab.next(), Ok(Some(1)));
+
+    let outer = lab.checkpoint();
+    assert_eq!(lab.next(), Ok(Some(2)));
+
+    let inner = lab.checkpoint();
+    assert_eq!(lab.next(), Ok(Some(3)));
+    assert_eq!(lab.next(), Ok(Some(4)));
+    assert_eq!(lab.rewind(inner), Ok(()));
+    assert_eq!(lab.next(), Ok(Some(3)));
+
+    assert_eq!(lab.rewind(outer), Ok(()));
+    assert_eq!(lab.checkpoints(), 0);
+    assert_eq!(lab.next(), Ok(Some(2)));
+    assert_eq!(lab.next(), Ok(Some(3)));
+    assert_eq!(lab.next(), Ok(Some(4)));
+}
+
+#[test]
+fn checkpoint_commit() {
+    let mut lab = [1, 2, 3, 4, 5].into_iter().into_fallible().buffered();
+
+    let outer = lab.checkpoint();
+    assert_eq!(lab.next(), Ok(Some(1)));
+    let inner = lab.checkpoint();
+    assert_eq!(lab.next(), Ok(Some(2)));
+    assert_eq!(lab.commit(inner), Ok(()));
+    assert_eq!(lab.next(), Ok(Some(3)));
+
+    let stale = lab.checkpoint();
+    assert_eq!(lab.commit(outer), Ok(()));
+    assert_eq!(lab.rewind(stale), Err(InvalidCheckpoint));
+    assert_eq!(lab.checkpoints(), 0);
+    assert_eq!(lab.next(), Ok(Some(4)));
+}
+
+#[test]
+fn sticky_errors() {
+    let source = [Ok(1), Ok(2), Err("bad token"), Ok(3)];
+
+    let mut lab = fallible_iterator::convert(source.into_iter()).buffered();
+    assert_eq!(lab.peek_n(3), Err("bad token"));
+    assert_eq!(lab.peek_n(3), Ok(None));
+
+    let mut lab = fallible_iterator::convert(source.into_iter())
+        .buffered()
+        .with_sticky_errors();
+    assert_eq!(lab.peek_n(3), Err("bad token"));
+    assert_eq!(lab.peek_n(2), Err("bad token"));
+    assert_eq!(lab.peek_n(1), Ok(Some(&2)));
+    assert_eq!(lab.next(), Ok(Some(1)));
+    assert_eq!(lab.next(), Ok(Some(2)));
+    assert_eq!(lab.peek(), Err("bad token"));
+    assert_eq!(lab.next(), Err("bad token"));
+    assert_eq!(lab.next(), Err("bad token"));
+    assert_eq!(lab.error(), Some(&"bad token"));
+}
+
+/// Yields `None` every other call.
+struct Flaky(u32);
+
+impl FallibleIterator for Flaky {
+    type Item = u32;
+    type Error = ();
+
+    fn next(&mut self) -> Result<Option<u32>, ()> {
+        self.0 += 1;
+        Ok(self.0.is_multiple_of(2).then_some(self.0))
+    }
+}
+
+#[test]
+fn exhaustion() {
+    let mut lab = Flaky(0).buffered();
+    assert!(!lab.is_exhausted());
+    assert_eq!(lab.peek(), Ok(None));
+    assert!(lab.is_exhausted());
+    assert_eq!(lab.peek_n(423423), Ok(None));
+    assert_eq!(lab.next(), Ok(None));
+    assert_eq!(lab.iter().0, 1);
+
+    lab.reset_exhaustion();
+    assert_eq!(lab.next(), Ok(Some(2)));
+    assert_eq!(lab.next(), Ok(None));
+    assert_eq!(lab.next(), Ok(None));
+    assert_eq!(lab.iter().0, 3);
+}
+
+#[test]
+fn positions() {
+    let mut lab = ["let", "x", "=", "42"]
+        .into_iter()
+        .into_fallible()
+        .buffered();
+    let mut ended = lab.clone();
+    assert_eq!(ended.nth(10), Ok(None));
+    assert_eq!(ended.position().index, 4);
+
+    let mut failing = fallible_iterator::convert([Ok(1), Ok(2), Err(())].into_iter()).buffered();
+    assert_eq!(failing.nth(5), Err(()));
+    assert_eq!(failing.position().index, 2);
+
+    assert_eq!(lab.advance_by(1), Ok(1));
+    assert_eq!(
+        lab.position(),
+        Position {
+            index: 1,
+            offset: 1
+        }
+    );
+    assert_eq!(
+        lab.peek_n_with_pos(2),
+        Ok(Some((
+            Position {
+                index: 3,
+                offset: 3
+            },
+            &"42"
+        )))
+    );
+
+    let mut lab = ["let", "x", "=", "42"]
+        .into_iter()
+        .into_fallible()
+        .buffered()
+        .with_width(|token| token.len());
+    assert_eq!(
+        lab.peek_n_with_pos(3),
+        Ok(Some((
+            Position {
+                index: 3,
+                offset: 5
+            },
+            &"42"
+        )))
+    );
+    assert_eq!(
+        lab.next_with_pos(),
+        Ok(Some((
+            Position {
+                index: 0,
+                offset: 0
+            },
+            "let"
+        )))
+    );
+
+    let checkpoint = lab.checkpoint();
+    assert_eq!(
+        lab.next_with_pos(),
+        Ok(Some((
+            Position {
+                index: 1,
+                offset: 3
+            },
+            "x"
+        )))
+    );
+    assert_eq!(
+        lab.position(),
+        Position {
+            index: 2,
+            offset: 4
+        }
+    );
+    assert_eq!(lab.rewind(checkpoint), Ok(()));
+    assert_eq!(
+        lab.position(),
+        Position {
+            index: 1,
+            offset: 3
+        }
+    );
+}
+
+#[test]
+fn next_if() {
+    let mut lab = [1, 2, 3, 4, 5].into_iter().into_fallible().buffered();
+    assert_eq!(lab.next_if(|&x| x == 2), Ok(None));
+    assert_eq!(lab.next_if(|&x| x == 1), Ok(Some(1)));
+    assert_eq!(lab.next_if_eq(&3), Ok(None));
+    assert_eq!(lab.next_if_eq(&2), Ok(Some(2)));
+    assert_eq!(lab.next_if_map(|&x| (x > 3).then_some(x * 10)), Ok(None));
+    assert_eq!(
+        lab.next_if_map(|&x| (x == 3).then_some(x * 10)),
+        Ok(Some(30))
+    );
+
+    assert_eq!(
+        lab.expect(|&x| x == 5, |found| (5, found.copied())),
+        Err(ExpectError::Unexpected((5, Some(4))))
+    );
+    assert_eq!(lab.expect(|&x| x == 4, |_| ()), Ok(4));
+    assert_eq!(lab.expect(|&x| x == 5, |_| ()), Ok(5));
+    assert_eq!(
+        lab.expect(|_| true, |found| found.copied()),
+        Err(ExpectError::Unexpected(None))
+    );
+}
+
+/// A deliberately naive [Storage], to check that the buffer does not rely on [VecDeque].
+#[derive(Default)]
+struct VecStorage<T>(Vec<T>);
+
+impl<T> Storage<T> for VecStorage<T> {
+    fn push_back(&mut self, item: T) {
+        self.0.push(item);
+    }
+
+    fn push_front(&mut self, item: T) {
+        self.0.insert(0, item);
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        (!self.0.is_empty()).then(|| self.0.remove(0))
+    }
+
+    fn insert(&mut self, index: usize, item: T) {
+        self.0.insert(index, item);
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T> {
+        (index < self.0.len()).then(|| self.0.remove(index))
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        self.0.get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.0.get_mut(index)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[test]
+fn custom_storage() {
+    let iter = [1, 2, 3, 4, 5].into_iter().into_fallible();
+    let mut lab = LookaheadBuffer::from_parts(iter, VecStorage(vec![0]));
+
+    assert_eq!(lab.peek_n(2), Ok(Some(&2)));
+    assert_eq!(lab.queue().0, [0, 1, 2]);
+    assert_eq!(lab.next(), Ok(Some(0)));
+
+    let checkpoint = lab.checkpoint();
+    assert_eq!(lab.nth(1), Ok(Some(2)));
+    assert_eq!(lab.rewind(checkpoint), Ok(()));
+    assert_eq!(lab.collect::<Vec<_>>(), Ok(vec![1, 2, 3, 4, 5]));
+}
+
+#[test]
+fn ring_storage() {
+    let iter = [1, 2, 3, 4, 5].into_iter().into_fallible();
+    let mut lab = LookaheadBuffer::from_parts(iter, RingBuffer::<_, 3>::new());
+
+    assert_eq!(lab.peek_n(2), Ok(Some(&3)));
+    assert_eq!(lab.next(), Ok(Some(1)));
+
+    let checkpoint = lab.checkpoint();
+    assert_eq!(lab.replace_next(1, [20, 21]), Ok(1));
+    assert_eq!(lab.queue().iter().collect::<Vec<_>>(), [&20, &21, &3]);
+    assert_eq!(lab.rewind(checkpoint), Ok(()));
+    assert_eq!(lab.collect::<Vec<_>>(), Ok(vec![2, 3, 4, 5]));
+
+    let iter = [1, 2, 3, 4, 5].into_iter().into_fallible();
+    let mut lab = LookaheadBuffer::from_parts(iter, RingBuffer::<_, 2>::new());
+    assert_eq!(lab.peek_n(5), Ok(None));
+    assert_eq!(lab.peek_n(1), Ok(Some(&2)));
+
+    let iter = [1, 2, 3]
+        .into_iter()
+        .into_fallible()
+        .map_err(LookaheadError::Source);
+    let mut lab = LookaheadBuffer::from_parts(iter, RingBuffer::<_, 2>::new());
+    lab.set_limit(Some(8));
+    assert_eq!(
+        lab.peek_n(5),
+        Err(LookaheadError::LimitExceeded {
+            requested: 6,
+            limit: 2
+        })
+    );
+
+    let mut ring = RingBuffer::<_, 2>::new();
+    assert_eq!(ring.push_back(2), Ok(()));
+    assert_eq!(ring.push_front(1), Ok(()));
+    assert_eq!(ring.insert(1, 3), Err(3));
+    assert_eq!(ring.remove(0), Some(1));
+    assert_eq!(ring.insert(0, 0), Ok(()));
+    assert_eq!(ring.iter().collect::<Vec<_>>(), [&0, &2]);
+}
+
+#[test]
+fn peek_many_mut() {
+    let mut lab = [1, 2, 3, 4, 5].into_iter().into_fallible().buffered();
+    assert_eq!(lab.peek_n(4), Ok(Some(&5)));
+
+    let [a, b] = lab.peek_many_mut([3, 0]).unwrap();
+    core::mem::swap(a, b);
+    *b *= 10;
+
+    assert_eq!(
+        lab.peek_many_mut([1, 1]),
+        Err(PeekManyError::OverlappingIndices)
+    );
+    assert_eq!(
+        lab.peek_many_mut([0, 5]),
+        Err(PeekManyError::IndexOutOfBounds)
+    );
+    assert_eq!(lab.collect::<Vec<_>>(), Ok(vec![40, 2, 3, 1, 5]));
+}
+
+#[test]
+fn pushback() {
+    let mut lab = [">>", "x"].into_iter().into_fallible().buffered();
+    let checkpoint = lab.checkpoint();
+
+    assert_eq!(lab.next(), Ok(Some(">>")));
+    lab.unread_many([">", ">"]);
+    assert_eq!(
+        lab.position(),
+        Position {
+            index: 0,
+            offset: 0
+        }
+    );
+    assert_eq!(
+        lab.next_with_pos(),
+        Ok(Some((
+            Position {
+                index: 0,
+                offset: 0
+            },
+            ">"
+        )))
+    );
+    assert_eq!(
+        lab.next_with_pos(),
+        Ok(Some((
+            Position {
+                index: 1,
+                offset: 1
+            },
+            ">"
+        )))
+    );
+
+    lab.push_front("y");
+    assert_eq!(lab.replace_next(1, ["a", "b", "c"]), Ok(1));
+    assert_eq!(
+        lab.peek_multiple::<4>(),
+        Ok([Some(&"a"), Some(&"b"), Some(&"c"), Some(&"x")])
+    );
+
+    assert_eq!(lab.rewind(checkpoint), Ok(()));
+    assert_eq!(
+        lab.position(),
+        Position {
+            index: 0,
+            offset: 0
+        }
+    );
+    assert_eq!(lab.collect::<Vec<_>>(), Ok(vec![">>", "x"]));
+}
+
+#[test]
+fn history() {
+    let mut lab = ["a", "/", "b", "/", "c"]
+        .into_iter()
+        .into_fallible()
+        .buffered()
+        .with_history(2);
+    assert_eq!(lab.look_back(0), None);
+    assert_eq!(lab.advance_by(3), Ok(3));
+    assert_eq!(lab.look_back(0), Some(&"b"));
+    assert_eq!(lab.look_back(1), Some(&"/"));
+    assert_eq!(lab.look_back(2), None);
+    assert_eq!(lab.look_back(usize::MAX), None);
+
+    let checkpoint = lab.checkpoint();
+    assert_eq!(lab.next(), Ok(Some("/")));
+    assert_eq!(lab.history(), &["b", "/"]);
+    assert_eq!(lab.rewind(checkpoint), Ok(()));
+    assert_eq!(lab.history(), &["/", "b"]);
+
+    let mut lab = ["a", "/", "b"]
+        .into_iter()
+        .into_fallible()
+        .buffered_const::<1>()
+        .with_history::<2>();
+    assert_eq!(lab.nth(2), Ok(Some("b")));
+    assert_eq!(lab.look_back(0), Some(&"b"));
+    assert_eq!(lab.look_back(1), Some(&"/"));
+    assert_eq!(lab.look_back(2), None);
+    assert_eq!(lab.look_back(usize::MAX), None);
+}
+
+#[test]
+fn limit() {
+    let mut lab = (0..).into_fallible().buffered_limited(4);
+    assert_eq!(lab.peek_n(3), Ok(Some(&3)));
+    assert_eq!(
+        lab.peek_n(usize::MAX - 1),
+        Err(LookaheadError::LimitExceeded {
+            requested: usize::MAX,
+            limit: 4
+        })
+    );
+    assert_eq!(lab.queue().len(), 4);
+
+    lab.with_limit(8, |lab| assert_eq!(lab.peek_n(7), Ok(Some(&7))));
+    assert_eq!(lab.limit(), Some(4));
+    assert_eq!(lab.peek_n(4), Ok(Some(&4)));
+    assert!(lab.peek_n(8).is_err());
+
+    extern crate std;
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        lab.with_limit(16, |_| panic!("parser bug"));
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(lab.limit(), Some(4));
+
+    lab.set_limit(None);
+    assert_eq!(lab.peek_n(100), Ok(Some(&100)));
+
+    let mut lab = (0..).into_fallible().buffered_limited(2);
+    lab.unread_many([7, 8, 9]);
+    assert_eq!(lab.peek_n(2), Ok(Some(&9)));
+    assert!(lab.peek_n(3).is_err());
+}
+
+#[test]
+fn peek_iter() {
+    let mut lab = ["f", "(", "a", ")", ";"]
+        .into_iter()
+        .into_fallible()
+        .buffered();
+
+    let mut iter = lab.peek_iter();
+    assert_eq!(iter.next(), Ok(Some(&"f")));
+    assert_eq!(iter.next(), Ok(Some(&"(")));
+    assert_eq!(iter.index(), 2);
+    assert_eq!(lab.queue().len(), 2);
+
+    assert_eq!(lab.position_ahead(|&t| t == ")" || t == ";"), Ok(Some(3)));
+    assert_eq!(lab.find_ahead(|t| t.len() > 1), Ok(None));
+    assert_eq!(lab.position(), Position::default());
+    assert_eq!(lab.next(), Ok(Some("f")));
+}
+
+#[test]
+fn peek_slice() {
+    let mut lab = (0..10).into_fallible().buffered();
+    assert_eq!(lab.advance_by(2), Ok(2));
+
+    // Wrap the queue around.
+    assert_eq!(lab.peek_n(5), Ok(Some(&7)));
+    lab.push_front(1);
+    assert_eq!(lab.peek_slice(3), Ok(&[1, 2, 3][..]));
+
+    lab.peek_slice_mut(2).unwrap()[1] = 20;
+    assert!(matches!(lab.peek_slice(3), Ok([1, 20, 3])));
+    assert_eq!(lab.peek_slice(100).map(<[_]>::len), Ok(9));
+}
+
+#[test]
+fn sequences() {
+    let mut lab = ["is", "not", "in", "x"]
+        .into_iter()
+        .into_fallible()
+        .buffered();
+    assert_eq!(lab.starts_with(&["is", "not"]), Ok(SequenceMatch::Full));
+    assert_eq!(
+        lab.eat_sequence(&["is", "in"]),
+        Ok(SequenceMatch::Partial(1))
+    );
+    assert_eq!(lab.eat_sequence(&["not"]), Ok(SequenceMatch::Partial(0)));
+    assert_eq!(lab.eat_sequence(&["is"]), Ok(SequenceMatch::Full));
+    assert_eq!(
+        lab.starts_with_by(&[&|t| t.len() == 3, &|&t| t == "in", &|_| true, &|_| true]),
+        Ok(SequenceMatch::Partial(3))
+    );
+    assert_eq!(
+        lab.eat_sequence_by(&[&|&t| t == "not", &|&t| t == "in"]),
+        Ok(SequenceMatch::Full)
+    );
+    assert_eq!(lab.next(), Ok(Some("x")));
+
+    let source = [Ok(1), Ok(2), Err("e")];
+    let mut lab = fallible_iterator::convert(source.into_iter())
+        .buffered()
+        .with_sticky_errors();
+    assert_eq!(lab.starts_with(&[9, 2, 3]), Ok(SequenceMatch::Partial(0)));
+    assert_eq!(lab.eat_sequence(&[1, 9, 3]), Ok(SequenceMatch::Partial(1)));
+    assert_eq!(lab.starts_with(&[1, 2, 3]), Err("e"));
+}
+
+#[test]
+fn iter_buffer() {
+    let mut lab = [1, 2, 3, 4, 5].into_iter().buffered_iter();
+    assert_eq!(lab.peek(), Some(&1));
+    assert_eq!(lab.peek_n(2), Some(&3));
+    assert_eq!(lab.peek_multiple::<2>(), [Some(&1), Some(&2)]);
+    assert_eq!(lab.next_if(|&x| x == 1), Some(1));
+
+    let checkpoint = lab.buffer_mut().checkpoint();
+    assert_eq!(lab.by_ref().take(2).collect::<Vec<_>>(), [2, 3]);
+    assert_eq!(lab.buffer_mut().rewind(checkpoint), Ok(()));
+    assert_eq!(lab.sum::<i32>(), 14);
+}
+
+#[test]
+fn result_items() {
+    let source = [Ok(1), Err("bad token"), Ok(2)];
+
+    let mut lab = LookaheadBuffer::from_results(source);
+    assert_eq!(lab.next(), Ok(Some(1)));
+    assert_eq!(lab.peek(), Err("bad token"));
+
+    let mut lab = source.into_iter().buffered_results().with_sticky_errors();
+    assert_eq!(lab.peek_n(1), Err("bad token"));
+    assert_eq!(lab.next(), Ok(Some(1)));
+    assert_eq!(lab.next(), Err("bad token"));
+
+    let mut lab = source.into_iter().buffered_iter();
+    assert_eq!(lab.peek_n(1), Some(&Err("bad token")));
+    assert_eq!(lab.flatten().collect::<Vec<_>>(), [1, 2]);
+}
+
+#[test]
+fn recovering() {
+    let source = [
+        Ok("let"),
+        Err("unterminated string"),
+        Ok(";"),
+        Err("bad char"),
+    ];
+
+    let mut lab =
+        fallible_iterator::convert(source.into_iter()).buffered_recovering(|_| Some("<error>"));
+    assert_eq!(lab.peek_n(3), Ok(Some(&"<error>")));
+    assert_eq!(
+        lab.diagnostics(),
+        [
+            Diagnostic {
+                index: 1,
+                error: "unterminated string"
+            },
+            Diagnostic {
+                index: 3,
+                error: "bad char"
+            }
+        ]
+    );
+    assert_eq!(lab.by_ref().count(), Ok(4));
+    assert_eq!(lab.take_diagnostics().len(), 2);
+    assert!(lab.diagnostics().is_empty());
+    assert!(!lab.is_aborted());
+
+    let source = [Ok("let"), Err("bad char"), Err("broken pipe"), Ok(";")];
+    let mut lab = fallible_iterator::convert(source.into_iter())
+        .buffered_recovering(|&e| (e != "broken pipe").then_some("<error>"));
+    assert_eq!(lab.peek_n(1000), Ok(None));
+    assert_eq!(lab.by_ref().count(), Ok(2));
+    assert!(lab.is_aborted());
+    assert_eq!(
+        lab.diagnostics().last(),
+        Some(&Diagnostic {
+            index: 2,
+            error: "broken pipe"
+        })
+    );
+    assert_eq!(lab.diagnostics().len(), 2);
+}
+
+#[test]
+fn eof_sentinel() {
+    let mut lab = ["a", "b"]
+        .into_iter()
+        .into_fallible()
+        .buffered()
+        .with_eof("<eof>");
+    assert_eq!(lab.peek_multiple::<3>(), Ok([&"a", &"b", &"<eof>"]));
+    assert_eq!(lab.peek_n(423423), Ok(&"<eof>"));
+    assert_eq!(lab.next(), Ok("a"));
+    assert_eq!(lab.is_eof(), Ok(false));
+    assert_eq!(lab.next(), Ok("b"));
+    assert_eq!(lab.is_eof(), Ok(true));
+    assert_eq!(lab.peek(), Ok(&"<eof>"));
+    assert_eq!(lab.next(), Ok("<eof>"));
+    assert_eq!(lab.next(), Ok("<eof>"));
+}
+
+#[test]
+fn exact() {
+    let mut lab = [1, 2, 3, 4, 5].into_iter().into_fallible().buffered();
+    assert_eq!(lab.peek_exact::<2>(), Ok(Some([&1, &2])));
+    assert_eq!(lab.next_exact::<3>(), Ok(Some([1, 2, 3])));
+    assert_eq!(lab.position().index, 3);
+    assert_eq!(lab.peek_exact::<3>(), Ok(None));
+    assert_eq!(lab.next_exact::<3>(), Ok(None));
+    assert!(matches!(lab.next_exact(), Ok(Some([4, 5]))));
+}
+
+#[test]
+fn shared_forks() {
+    let source = [Ok(1), Ok(2), Ok(3), Err("bad token")];
+
+    let mut a = fallible_iterator::convert(source.into_iter()).buffered_shared();
+    assert_eq!(a.next(), Ok(Some(1)));
+
+    let mut b = a.fork();
+    assert_eq!(b.cursors(), 2);
+    assert_eq!(a.next(), Ok(Some(2)));
+    assert_eq!(a.next(), Ok(Some(3)));
+    assert_eq!(a.peek(), Err("bad token"));
+    assert_eq!(a.index(), 3);
+
+    assert_eq!(b.peek(), Ok(Some(2)));
+
+    let first = b.peek();
+    let second = b.peek_n(1);
+    assert_eq!(a.next(), Err("bad token"));
+    assert_eq!((first, second), (Ok(Some(2)), Ok(Some(3))));
+    assert_eq!(b.next(), Ok(Some(2)));
+
+    let mut c = b.fork();
+    drop(b);
+    assert_eq!(c.cursors(), 2);
+    assert_eq!(c.next(), Ok(Some(3)));
+    assert_eq!(c.next(), Err("bad token"));
+    assert_eq!(a.next(), Err("bad token"));
+}
+
+#[test]
+fn tee() {
+    let source = [Ok(1), Ok(2), Ok(3), Err("bad token")];
+
+    let mut lab = fallible_iterator::convert(source.into_iter()).buffered();
+    assert_eq!(lab.peek_n(1), Ok(Some(&2)));
+    assert_eq!(lab.next(), Ok(Some(1)));
+
+    let checkpoint = lab.checkpoint();
+    let Err(mut lab) = lab.tee() else {
+        panic!("tee must fail while a checkpoint is live");
+    };
+    assert_eq!(lab.commit(checkpoint), Ok(()));
+
+    let Ok((mut highlighter, mut parser)) = lab.tee() else {
+        panic!("tee must succeed without checkpoints");
+    };
+    assert_eq!(highlighter.next(), Ok(Some(2)));
+    assert_eq!(highlighter.next(), Ok(Some(3)));
+    assert_eq!(highlighter.next(), Err("bad token"));
+    assert_eq!(highlighter.index(), 2);
+
+    assert_eq!(parser.peek_n(1), Ok(Some(3)));
+    assert_eq!(parser.next(), Ok(Some(2)));
+    assert_eq!(parser.next(), Ok(Some(3)));
+    assert_eq!(parser.next(), Err("bad token"));
+    assert_eq!(highlighter.next(), Err("bad token"));
+}
+
+#[test]
+fn source_stack() {
+    let root = vec!["a", "include", "b"].into_iter().into_fallible();
+    let mut lab = SourceStack::new(root).with_depth_limit(2).buffered();
+
+    assert_eq!(lab.peek_n(2).unwrap().map(|t| t.item), Some("b"));
+    assert_eq!(lab.next().unwrap().map(|t| t.item), Some("a"));
+    assert_eq!(lab.next().unwrap().map(|t| t.item), Some("include"));
+
+    let included = vec!["x", "y"].into_iter().into_fallible();
+    assert_eq!(lab.push_source(included), Ok(1));
+    assert_eq!(lab.depth(), 2);
+
+    let recursive = vec!["z"].into_iter().into_fallible();
+    assert_eq!(
+        lab.push_source(recursive),
+        Err(PushSourceError::DepthLimitExceeded(DepthLimitExceeded {
+            limit: 2
+        }))
+    );
+
+    assert_eq!(
+        lab.peek_multiple::<4>(),
+        Ok([
+            Some(&Sourced {
+                source: 1,
+                item: "x"
+            }),
+            Some(&Sourced {
+                source: 1,
+                item: "y"
+            }),
+            Some(&Sourced {
+                source: 0,
+                item: "b"
+            }),
+            None,
+        ])
+    );
+    assert_eq!(
+        lab.by_ref().map(|t| Ok(t.item)).collect::<Vec<_>>(),
+        Ok(vec!["x", "y", "b"])
+    );
+
+    let trailing = vec!["end"].into_iter().into_fallible();
+    assert_eq!(lab.push_source(trailing), Ok(2));
+    assert_eq!(
+        lab.next(),
+        Ok(Some(Sourced {
+            source: 2,
+            item: "end"
+        }))
+    );
+
+    let root = vec![1, 2, 3].into_iter().into_fallible();
+    let mut lab = SourceStack::new(root).buffered();
+    assert_eq!(lab.next().unwrap().map(|t| t.item), Some(1));
+
+    let checkpoint = lab.checkpoint();
+    assert_eq!(lab.replace_next(1, [Sourced { source: 0, item: 9 }]), Ok(1));
+    let included = vec![10].into_iter().into_fallible();
+    assert_eq!(
+        lab.push_source(included),
+        Err(PushSourceError::CheckpointLive)
+    );
+    assert_eq!(lab.rewind(checkpoint), Ok(()));
+    let items = lab.map(|t| Ok(t.item)).collect::<Vec<_>>();
+    assert_eq!(items, Ok(vec![2, 3]));
+
+    let root = fallible_iterator::convert(vec![Ok(1), Err("bad token")].into_iter());
+    let mut lab = SourceStack::new(root).buffered().with_sticky_errors();
+    assert_eq!(lab.peek_n(1).map(|_| ()), Err("bad token"));
+    let included = fallible_iterator::convert(vec![Ok(10)].into_iter());
+    assert_eq!(lab.push_source(included), Err(PushSourceError::StoredError));
+
+    let root = vec!["a", "b"].into_iter().into_fallible();
+    let mut lab = SourceStack::new(root).with_depth_limit(2).buffered();
+    assert_eq!(lab.peek().unwrap().map(|t| t.item), Some("a"));
+    assert_eq!(
+        lab.push_source(vec!["x"].into_iter().into_fallible()),
+        Ok(1)
+    );
+    assert_eq!(lab.peek_n(1).unwrap().map(|t| t.item), Some("a"));
+    assert_eq!(lab.depth(), 1);
+
+    assert_eq!(lab.next().unwrap().map(|t| t.item), Some("x"));
+    assert_eq!(lab.next().unwrap().map(|t| t.item), Some("a"));
+    assert_eq!(
+        lab.push_source(vec!["y"].into_iter().into_fallible()),
+        Ok(2)
+    );
+    assert_eq!(lab.next().unwrap().map(|t| t.item), Some("y"));
+    assert_eq!(
+        lab.push_source(vec!["z"].into_iter().into_fallible()),
+        Ok(3)
+    );
+    let items = lab.map(|t| Ok(t.item)).collect::<Vec<_>>();
+    assert_eq!(items, Ok(vec!["z", "b"]));
+}
+
+#[test]
+fn splice() {
+    let mut lab = [1, 2, 3, 4, 5].into_iter().into_fallible().buffered();
+    assert_eq!(lab.next(), Ok(Some(1)));
+
+    let checkpoint = lab.checkpoint();
+    assert_eq!(lab.splice(1..3, [30, 31, 32]), Ok(2));
+    assert_eq!(lab.queue().len(), 4);
+    assert_eq!(lab.splice(..=0, []), Ok(1));
+    assert_eq!(lab.position().index, 1);
+    assert_eq!(
+        lab.peek_multiple::<4>(),
+        Ok([Some(&30), Some(&31), Some(&32), Some(&5)])
+    );
+
+    assert_eq!(lab.splice(4.., [6, 7]), Ok(0));
+    assert_eq!(lab.splice(3.., [50]), Ok(3));
+    assert_eq!(lab.clone().collect::<Vec<_>>(), Ok(vec![30, 31, 32, 50]));
+
+    assert_eq!(lab.rewind(checkpoint), Ok(()));
+    assert_eq!(lab.collect::<Vec<_>>(), Ok(vec![2, 3, 4, 5]));
+
+    let mut lab = (0..).into_fallible().buffered_limited(8);
+    assert_eq!(lab.peek_n(2), Ok(Some(&2)));
+    assert_eq!(lab.splice(1.., [9]), Ok(2));
+    assert_eq!(lab.queue().len(), 2);
+    assert_eq!(lab.splice(4.., [10]), Ok(0));
+    assert_eq!(
+        lab.peek_multiple::<5>(),
+        Ok([Some(&0), Some(&9), Some(&3), Some(&4), Some(&10)])
+    );
+
+    let mut lab = [1, 2, 3].into_iter().into_fallible().buffered();
+    let expansion = (10..).take(2).map(|x| x + 1);
+    assert_eq!(lab.replace_next(1, expansion), Ok(1));
+    assert_eq!(lab.collect::<Vec<_>>(), Ok(vec![11, 12, 2, 3]));
+}
+
+#[test]
+fn trivia() {
+    let tokens = [
+        " ", "let", " ", "x", " ", "=", " ", "1", ";", " ", "// done", "\n",
+    ];
+    let mut lab = tokens
+        .into_iter()
+        .into_fallible()
+        .buffered()
+        .with_trivia(|t: &&str| t.trim().is_empty() || t.starts_with("//"));
+
+    assert_eq!(lab.peek(), Ok(Some(&"let")));
+    assert_eq!(lab.peek_n(2), Ok(Some(&"=")));
+    assert_eq!(
+        lab.next(),
+        Ok(Some(WithTrivia {
+            leading: vec![" "],
+            item: "let",
+            trailing: vec![],
+        }))
+    );
+
+    let rest = lab.by_ref().collect::<Vec<_>>().unwrap();
+    assert_eq!(rest.len(), 4);
+    assert_eq!(rest[3].trailing, [" ", "// done", "\n"]);
+
+    let items = rest.into_iter().flat_map(WithTrivia::into_items);
+    assert_eq!(
+        items.collect::<alloc::string::String>(),
+        " x = 1; // done\n"
+    );
+    assert_eq!(lab.take_trivia(), Ok(vec![]));
+
+    let is_trivia = |t: &&str| t.trim().is_empty();
+    let source = [Ok("a"), Ok(" "), Err("e")];
+    let mut lab = fallible_iterator::convert(source.into_iter())
+        .buffered()
+        .with_sticky_errors()
+        .with_trivia(is_trivia);
+    assert_eq!(lab.peek(), Ok(Some(&"a")));
+    assert_eq!(
+        lab.next(),
+        Ok(Some(WithTrivia {
+            leading: vec![],
+            item: "a",
+            trailing: vec![],
+        }))
+    );
+    assert_eq!(lab.next(), Err("e"));
+    assert_eq!(lab.buffer().queue().len(), 1);
+
+    let source = [Ok("a"), Ok("b"), Err("e")];
+    let mut lab = fallible_iterator::convert(source.into_iter())
+        .buffered()
+        .with_sticky_errors()
+        .with_trivia(is_trivia);
+    assert_eq!(lab.next().map(|t| t.map(|t| t.item)), Ok(Some("a")));
+    assert_eq!(lab.next().map(|t| t.map(|t| t.item)), Ok(Some("b")));
+    assert_eq!(lab.next(), Err("e"));
+
+    let source = [Ok("a"), Ok(" "), Err("e"), Ok("b")];
+    let mut lab = fallible_iterator::convert(source.into_iter())
+        .buffered()
+        .with_trivia(is_trivia);
+    assert_eq!(lab.next().map(|t| t.map(|t| t.item)), Ok(Some("a")));
+    assert_eq!(lab.next(), Err("e"));
+    assert_eq!(
+        lab.next(),
+        Ok(Some(WithTrivia {
+            leading: vec![" "],
+            item: "b",
+            trailing: vec![],
+        }))
+    );
+}
@@ -0,0 +1,203 @@
+use crate::{LookaheadBuffer, Storage};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use fallible_iterator::FallibleIterator;
+
+/// A [LookaheadBuffer] that skips trivia, like whitespace and comments, when peeking,
+/// created by [LookaheadBuffer::with_trivia].
+///
+/// Trivia is not dropped: every significant item is consumed together with the trivia
+/// around it as [WithTrivia], so the input can be reconstructed losslessly.
+pub struct TriviaFilter<I: FallibleIterator, F, S = VecDeque<<I as FallibleIterator>::Item>> {
+    buffer: LookaheadBuffer<I, S>,
+    is_trivia: F,
+
+    /// An error from looking past the last consumed item, returned by the next call.
+    error: Option<I::Error>,
+}
+
+/// A significant item with the trivia around it, yielded by [TriviaFilter].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WithTrivia<T> {
+    /// The trivia between the previous significant item and this one.
+    pub leading: Vec<T>,
+
+    /// The significant item.
+    pub item: T,
+
+    /// The trivia after this item, if it is the last significant item. Empty otherwise,
+    /// since that trivia is the leading trivia of the next item.
+    pub trailing: Vec<T>,
+}
+
+impl<T> WithTrivia<T> {
+    /// Returns all items in order: the leading trivia, the item and the trailing trivia.
+    #[inline]
+    pub fn into_items(self) -> impl Iterator<Item = T> {
+        self.leading
+            .into_iter()
+            .chain(core::iter::once(self.item))
+            .chain(self.trailing)
+    }
+}
+
+impl<I: FallibleIterator, S: Storage<I::Item>> LookaheadBuffer<I, S> {
+    /// Wraps `self` in a [TriviaFilter], which skips all items `is_trivia` returns `true` for
+    /// when peeking.
+    #[inline]
+    #[must_use]
+    pub const fn with_trivia<F: Fn(&I::Item) -> bool>(self, is_trivia: F) -> TriviaFilter<I, F, S> {
+        TriviaFilter {
+            buffer: self,
+            is_trivia,
+            error: None,
+        }
+    }
+}
+
+impl<I: FallibleIterator, F: Fn(&I::Item) -> bool, S: Storage<I::Item>> TriviaFilter<I, F, S> {
+    /// Returns a reference to the underlying [LookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub const fn buffer(&self) -> &LookaheadBuffer<I, S> {
+        &self.buffer
+    }
+
+    /// Returns a mutable reference to the underlying [LookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub const fn buffer_mut(&mut self) -> &mut LookaheadBuffer<I, S> {
+        &mut self.buffer
+    }
+
+    /// Returns the underlying [LookaheadBuffer].
+    #[inline]
+    #[must_use]
+    pub fn into_buffer(self) -> LookaheadBuffer<I, S> {
+        self.buffer
+    }
+
+    /// Returns the index of the nth significant item in the lookahead window of the buffer.
+    /// Fails with the error left over from [TriviaFilter::next] first, if any.
+    fn significant_index(&mut self, n: usize) -> Result<Option<usize>, I::Error> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        let mut remaining = n;
+        let mut index = 0;
+
+        while let Some(token) = self.buffer.peek_n(index)? {
+            if !(self.is_trivia)(token) {
+                if remaining == 0 {
+                    return Ok(Some(index));
+                }
+
+                remaining -= 1;
+            }
+
+            index += 1;
+        }
+
+        Ok(None)
+    }
+
+    /// Consumes the next `n` items of the buffer.
+    fn take(&mut self, n: usize) -> Result<Vec<I::Item>, I::Error> {
+        let mut items = Vec::with_capacity(n);
+
+        for _ in 0..n {
+            if let Some(token) = self.buffer.next()? {
+                items.push(token);
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Peeks into the next significant item. Does not advance. Equivalent to `self.peek_n(0)`.
+    #[inline]
+    pub fn peek(&mut self) -> Result<Option<&I::Item>, I::Error> {
+        self.peek_n(0)
+    }
+
+    /// Peeks into the nth significant item, with n=0 being the next one. Trivia is not counted.
+    pub fn peek_n(&mut self, n: usize) -> Result<Option<&I::Item>, I::Error> {
+        match self.significant_index(n)? {
+            Some(index) => self.buffer.peek_n(index),
+            None => Ok(None),
+        }
+    }
+
+    /// Consumes the next significant item together with its trivia.
+    ///
+    /// If only trivia is left, [None] is returned and the trivia is kept. Use
+    /// [TriviaFilter::take_trivia] to consume it.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<WithTrivia<I::Item>>, I::Error> {
+        let Some(index) = self.significant_index(0)? else {
+            return Ok(None);
+        };
+
+        let leading = self.take(index)?;
+        let Some(item) = self.buffer.next()? else {
+            return Ok(None);
+        };
+
+        // Every item after the last significant item is trivia and is consumed with it. If
+        // looking ahead fails, the trivia stays queued as the leading trivia of the next call,
+        // which returns the error.
+        let trailing = match self.significant_index(0) {
+            Ok(None) => {
+                let count = self.buffer.queue().len();
+                self.take(count)?
+            }
+            Ok(Some(_)) => Vec::new(),
+            Err(error) => {
+                self.error = Some(error);
+                Vec::new()
+            }
+        };
+
+        Ok(Some(WithTrivia {
+            leading,
+            item,
+            trailing,
+        }))
+    }
+
+    /// Consumes and returns the trivia before the next significant item.
+    pub fn take_trivia(&mut self) -> Result<Vec<I::Item>, I::Error> {
+        let count = match self.significant_index(0)? {
+            Some(index) => index,
+            None => self.buffer.queue().len(),
+        };
+
+        self.take(count)
+    }
+}
+
+impl<I: FallibleIterator, F: Fn(&I::Item) -> bool, S: Storage<I::Item>> FallibleIterator
+    for TriviaFilter<I, F, S>
+{
+    type Item = WithTrivia<I::Item>;
+    type Error = I::Error;
+
+    #[inline]
+    fn next(&mut self) -> Result<Option<WithTrivia<I::Item>>, I::Error> {
+        TriviaFilter::next(self)
+    }
+}
+
+impl<T: Clone, I: FallibleIterator<Item = T, Error: Clone> + Clone, F: Clone, S: Storage<T> + Clone>
+    Clone for TriviaFilter<I, F, S>
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+            is_trivia: self.is_trivia.clone(),
+            error: self.error.clone(),
+        }
+    }
+}